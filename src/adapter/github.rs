@@ -1,34 +1,232 @@
 use std::error::Error;
-use regex::Regex;
+use hmac::{Hmac, Mac};
+use reqwest::Method;
+use sha2::Sha256;
+use serde::de::DeserializeOwned;
 
-use crate::domain::model::github::Issue;
+use crate::domain::model::github::{
+    CheckRuns, CiStatus, Commit, Issue, IssueReference, Label, NewIssue, OAuthTokenResponse, PullRequest, PullRequestFile,
+    PullRequestHead, Repository, RepositoryPermission, SearchResult, User, UserSearchResult,
+};
 
 pub struct Github;
 
 impl Github {
 
-    pub async fn fetch_issue(&self, text: &str) -> Result<Issue, Box<dyn Error>> {
-        let re = Regex::new(r"(?P<owner>[\w.-]*)\/(?P<repo>[\w.-]*)\#(?P<issue_number>\d*)").unwrap();
+    /// Checks `X-Hub-Signature-256`, the `sha256=` HMAC of the raw payload keyed with the webhook secret.
+    pub fn verify_signature(&self, secret: &str, body: &str, signature: &str) -> bool {
+        let signature = match signature.strip_prefix("sha256=").map(hex::decode) {
+            Some(Ok(signature)) => signature,
+            _ => return false,
+        };
 
-        let mut url = String::new();
+        let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+            Ok(mac) => mac,
+            Err(_) => return false,
+        };
 
-        for caps in re.captures_iter(text) {
-            url = format!("https://api.github.com/repos/{}/{}/issues/{}", &caps["owner"], &caps["repo"], &caps["issue_number"]);
-        }
+        mac.update(body.as_bytes());
+
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    pub async fn fetch_issue(&self, token: &str, reference: &IssueReference) -> Result<Issue, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/issues/{}", reference.owner, reference.repo, reference.number);
+
+        self.get(token, &url).await
+    }
+
+    pub async fn fetch_pull_request(&self, token: &str, reference: &IssueReference) -> Result<PullRequest, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/pulls/{}", reference.owner, reference.repo, reference.number);
+
+        self.get(token, &url).await
+    }
+
+    pub async fn fetch_commit(&self, token: &str, owner: &str, repo: &str, sha: &str) -> Result<Commit, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/commits/{}", owner, repo, sha);
+
+        self.get(token, &url).await
+    }
+
+    pub async fn fetch_repository(&self, token: &str, owner: &str, repo: &str) -> Result<Repository, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}", owner, repo);
+
+        self.get(token, &url).await
+    }
+
+    pub async fn fetch_repositories(&self, token: &str) -> Result<Vec<Repository>, Box<dyn Error>> {
+        self.get_all(token, "https://api.github.com/user/repos?per_page=100&sort=pushed").await
+    }
+
+    pub async fn create_issue(&self, token: &str, owner: &str, repo: &str, issue: &NewIssue) -> Result<Issue, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/issues", owner, repo);
+
+        self.send(token, Method::POST, &url, serde_json::to_value(issue)?).await
+    }
+
+    pub async fn fetch_labels(&self, token: &str, owner: &str, repo: &str) -> Result<Vec<Label>, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/labels?per_page=100", owner, repo);
+
+        self.get(token, &url).await
+    }
+
+    pub async fn fetch_permission(&self, token: &str, owner: &str, repo: &str, login: &str) -> Result<RepositoryPermission, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/collaborators/{}/permission", owner, repo, login);
+
+        self.get(token, &url).await
+    }
+
+    pub async fn update_issue_state(&self, token: &str, reference: &IssueReference, state: &str) -> Result<Issue, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/issues/{}", reference.owner, reference.repo, reference.number);
+
+        self.send(token, Method::PATCH, &url, serde_json::json!({ "state": state })).await
+    }
+
+    pub async fn add_assignees(&self, token: &str, reference: &IssueReference, logins: &[&str]) -> Result<Issue, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/issues/{}/assignees", reference.owner, reference.repo, reference.number);
+
+        self.send(token, Method::POST, &url, serde_json::json!({ "assignees": logins })).await
+    }
+
+    pub async fn add_labels(&self, token: &str, reference: &IssueReference, labels: &[&str]) -> Result<Vec<Label>, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/issues/{}/labels", reference.owner, reference.repo, reference.number);
+
+        self.send(token, Method::POST, &url, serde_json::json!({ "labels": labels })).await
+    }
+
+    pub async fn search_issues(&self, token: &str, query: &str) -> Result<SearchResult, Box<dyn Error>> {
+        let url = reqwest::Url::parse_with_params(
+            "https://api.github.com/search/issues",
+            &[("q", query), ("per_page", "10"), ("sort", "updated")],
+        )?;
+
+        self.get(token, url.as_str()).await
+    }
+
+    pub async fn fetch_pull_request_files(&self, token: &str, reference: &IssueReference) -> Result<Vec<PullRequestFile>, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/pulls/{}/files?per_page=100", reference.owner, reference.repo, reference.number);
+
+        self.get_all(token, &url).await
+    }
+
+    pub async fn fetch_ci_status(&self, token: &str, reference: &IssueReference) -> Result<CiStatus, Box<dyn Error>> {
+        let url = format!("https://api.github.com/repos/{}/{}/pulls/{}", reference.owner, reference.repo, reference.number);
+        let pull_request: PullRequestHead = self.get(token, &url).await?;
+
+        let url = format!("https://api.github.com/repos/{}/{}/commits/{}/check-runs", reference.owner, reference.repo, pull_request.head.sha);
+        let check_runs: CheckRuns = self.get(token, &url).await?;
+
+        Ok(CiStatus::from_check_runs(&check_runs.check_runs))
+    }
 
+    pub async fn fetch_authenticated_user(&self, token: &str) -> Result<User, Box<dyn Error>> {
+        self.get(token, "https://api.github.com/user").await
+    }
+
+    /// Only finds users who made their email address public on their profile.
+    pub async fn search_users_by_email(&self, token: &str, email: &str) -> Result<Vec<User>, Box<dyn Error>> {
+        let url = reqwest::Url::parse_with_params(
+            "https://api.github.com/search/users",
+            &[("q", format!("{} in:email", email))],
+        )?;
+        let result: UserSearchResult = self.get(token, url.as_str()).await?;
 
+        Ok(result.items)
+    }
+
+    pub async fn oauth_access_token(&self, client_id: &str, client_secret: &str, code: &str) -> Result<String, Box<dyn Error>> {
         let client = reqwest::Client::builder()
             .user_agent("simple-worker-slack-bot")
             .build()?;
 
         let res = client
-            .get(url)
+            .post("https://github.com/login/oauth/access_token")
+            .header("Accept", "application/json")
+            .form(&[("client_id", client_id), ("client_secret", client_secret), ("code", code)])
             .send()
+            .await?
+            .error_for_status()?
+            .json::<OAuthTokenResponse>()
             .await?;
 
-        let issue = res.json::<Issue>()
+        match res.access_token {
+            Some(access_token) => Ok(access_token),
+            None => Err(format!("GitHub OAuth failed: {}", res.error.unwrap_or_default()).into()),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, token: &str, url: &str) -> Result<T, Box<dyn Error>> {
+        self.request(token, Method::GET, url, None).await
+    }
+
+    /// Follows `rel="next"` in the `Link` header until the last page.
+    async fn get_all<T: DeserializeOwned>(&self, token: &str, url: &str) -> Result<Vec<T>, Box<dyn Error>> {
+        let mut items = vec![];
+        let mut next = Some(url.to_string());
+
+        while let Some(url) = next {
+            let res = self.response(token, Method::GET, &url, None).await?;
+
+            next = res.headers().get("link").and_then(|link| link.to_str().ok()).and_then(next_page);
+            items.extend(res.json::<Vec<T>>().await?);
+        }
+
+        Ok(items)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, token: &str, method: Method, url: &str, body: serde_json::Value) -> Result<T, Box<dyn Error>> {
+        self.request(token, method, url, Some(body)).await
+    }
+
+    async fn request<T: DeserializeOwned>(&self, token: &str, method: Method, url: &str, body: Option<serde_json::Value>) -> Result<T, Box<dyn Error>> {
+        let payload = self.response(token, method, url, body)
+            .await?
+            .json::<T>()
             .await?;
 
-        Ok(issue)
+        Ok(payload)
+    }
+
+    async fn response(&self, token: &str, method: Method, url: &str, body: Option<serde_json::Value>) -> Result<reqwest::Response, Box<dyn Error>> {
+        let client = reqwest::Client::builder()
+            .user_agent("simple-worker-slack-bot")
+            .build()?;
+
+        let mut builder = client
+            .request(method, url)
+            .bearer_auth(token)
+            .header("Accept", "application/vnd.github+json");
+
+        if let Some(body) = body {
+            builder = builder.json(&body);
+        }
+
+        let res = builder
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(res)
+    }
+}
+
+/// The `rel="next"` URL of a `Link` header such as `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`.
+fn next_page(link: &str) -> Option<String> {
+    link.split(',')
+        .filter_map(|part| part.split_once(';'))
+        .find(|(_, params)| params.split(';').any(|param| param.trim() == r#"rel="next""#))
+        .map(|(url, _)| url.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+}
+
+#[cfg(test)]
+mod github_test {
+    use super::*;
+
+    #[test]
+    fn test_next_page() {
+        let link = r#"<https://api.github.com/repositories/1/pulls/2/files?per_page=100&page=2>; rel="next", <https://api.github.com/repositories/1/pulls/2/files?per_page=100&page=3>; rel="last""#;
+
+        assert_eq!(Some("https://api.github.com/repositories/1/pulls/2/files?per_page=100&page=2".to_string()), next_page(link));
+        assert_eq!(None, next_page(r#"<https://api.github.com/repositories/1/pulls/2/files?page=1>; rel="prev""#));
     }
 }
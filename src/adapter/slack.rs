@@ -1,243 +1,1717 @@
+use std::collections::HashMap;
 use std::error::Error;
 use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
 use regex::Regex;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::Sha256;
 
 use crate::domain::model::{
-    github::PayloadRepository,
-    routes::GithubWebhookRequest,
-    slack::{Accessory, Blocks, Message, Text, TextLine},
+    block_kit::{validate_blocks, Attachment, Block, ButtonStyle, ContextElement, Element, OptionObject, Text, MAX_MESSAGE_BLOCKS, MAX_OPTION_VALUE, MAX_SECTION_TEXT, MAX_VIEW_BLOCKS},
+    github::{CardState, CiStatus, Commit, Issue, IssueReference, Label, PayloadRepository, Repository, SearchItem, State},
+    mrkdwn::{escape, from_markdown, link, truncate, user_link},
+    quiet_hours::QuietHours,
+    digest::{Digest, Frequency},
+    rollup::{Queue, Rollup, RollupEntry},
+    routing::{RouteScope, RoutingTable},
+    subscription::{ChannelSubscriptions, Subscription},
+    routes::{AlertWebhookRequest, GithubWebhookRequest, NotificationReason, Release},
+    slack::{
+        ApiResponse, ConversationsOpenResponse, HomeViewBuilder, Message, OAuthAccessResponse, OpenedView, PostedMessage, PermalinkResponse, PostMessageRequest,
+        ReactionRequest, ResponseUrlMessage, ShortcutOrigin, SlackUser, TextLine, Unfurl, UnfurlRequest, UpdateMessageRequest,
+        UserInfoResponse, View, ViewsOpenRequest, ViewsOpenResponse, ViewsPublishRequest, ViewsUpdateRequest,
+    },
 };
 
+/// Bodies longer than this are cut and link to GitHub. Up to it they are spread over as many sections
+/// as they need, which keeps a card on a single page: updates and slash command replies have only one.
+const MAX_BODY_LENGTH: usize = 2 * MAX_SECTION_TEXT;
+
 pub struct Slack;
 
-impl Slack {
-    pub fn create_message<T: PayloadRepository>(&self, payload: &T, body: &GithubWebhookRequest) -> Message {
-        let text_lines = self.text_lines(
-            payload,
-            &format!("{}/{}#{}", body.repository.owner.login, body.repository.name, payload.number()),
-            &format!("An {} was {}", body.label(), body.action)
+impl Slack {
+    pub fn create_message<T: PayloadRepository>(&self, payload: &T, body: &GithubWebhookRequest, labels: &[Label]) -> Message {
+        let reference = IssueReference {
+            owner: body.repository.owner.login.to_string(),
+            repo: body.repository.name.to_string(),
+            number: payload.number(),
+        };
+
+        let status = self.card_status(payload).unwrap_or_else(|| format!("An {} was {}", body.label(), escape(&body.action)));
+        let text_lines = self.text_lines(payload, &reference.to_string(), &status);
+
+        Message {
+            text: format!("{}: {}", status, self.fallback_text(payload, &reference)),
+            ..self.construct_card(payload, body.card_state(payload), &text_lines, &reference, labels)
+        }
+    }
+
+    /// Renders an already posted card again from the latest payload, to be swapped in with `chat.update`.
+    pub fn create_card<T: PayloadRepository>(&self, payload: &T, reference: &IssueReference, labels: &[Label]) -> Message {
+        let text_lines = self.text_lines(payload, &reference.to_string(), &self.card_status(payload).unwrap_or_default());
+
+        self.construct_card(payload, payload.card_state(), &text_lines, reference, labels)
+    }
+
+    /// A header with the state of the item above its details, which sit next to a bar in the state colour.
+    fn construct_card<T: PayloadRepository>(&self, payload: &T, state: CardState, text_lines: &str, reference: &IssueReference, labels: &[Label]) -> Message {
+        let mut blocks = self.construct_message(payload, text_lines);
+
+        if !payload.labels().is_empty() {
+            blocks.push(self.construct_labels(payload.labels()));
+        }
+
+        blocks.push(self.construct_actions(payload, reference, labels));
+
+        Message {
+            blocks: vec![Block::header(&format!("{} {}", state.emoji(), state.header()))],
+            attachments: vec![Attachment::new(state.colour(), blocks)],
+            response_type: "in_channel".to_string(),
+            text: self.fallback_text(payload, reference),
+        }
+    }
+
+    pub fn construct_labels(&self, labels: &[Label]) -> Block {
+        let text = labels
+            .iter()
+            .map(|label| format!("{} {}", label.emoji(), escape(&label.name)))
+            .collect::<Vec<_>>()
+            .join("   ");
+
+        Block::context(vec![ContextElement::Text(Text::mrkdwn(&text))])
+    }
+
+    fn fallback_text<T: PayloadRepository>(&self, payload: &T, reference: &IssueReference) -> String {
+        format!("{} {}", reference, escape(payload.title()))
+    }
+
+    fn card_status<T: PayloadRepository>(&self, payload: &T) -> Option<String> {
+        payload.merged_by().map(|user| format!(":tada: Merged by {}", user_link(&user.html_url, &user.login)))
+    }
+
+    pub fn create_direct_message<T: PayloadRepository>(&self, payload: &T, reference: &IssueReference, body: &GithubWebhookRequest, reason: &NotificationReason) -> Message {
+        let item = format!("{} {}", link(payload.html_url(), &reference.to_string()), escape(payload.title()));
+
+        let text = match (reason, &body.comment) {
+            (NotificationReason::ReviewRequested, _) => format!(":eyes: Your review was requested on {}", item),
+            (NotificationReason::Assigned, _) => format!(":point_right: You were assigned to {}", item),
+            (NotificationReason::Mentioned, Some(comment)) => format!(":speech_balloon: You were {} in {}", link(&comment.html_url, "mentioned"), item),
+            (NotificationReason::Mentioned, None) => format!(":speech_balloon: You were mentioned in {}", item),
+        };
+
+        let text = match &body.sender {
+            Some(sender) => format!("{} by {}", text, user_link(&sender.html_url, &sender.login)),
+            None => text,
+        };
+
+        Message {
+            blocks: vec![Block::section(Text::mrkdwn(&text))],
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text,
+        }
+    }
+
+    /// Follow-up events are posted under the original card, so they only carry what changed.
+    pub fn create_thread_reply<T: PayloadRepository>(&self, payload: &T, body: &GithubWebhookRequest) -> Message {
+        let text = format!("An {} was {}: {}", body.label(), escape(&body.action), link(payload.html_url(), payload.title()));
+
+        Message {
+            blocks: vec![Block::section(Text::mrkdwn(&text))],
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text,
+        }
+    }
+
+    /// One message for the events held back since the last scheduled run, counts first.
+    pub fn create_rollup_message(&self, rollup: &Rollup, queue: Queue) -> Message {
+        let heading = match queue {
+            Queue::Rollup => "Bot activity",
+            Queue::CatchUp => "While this channel was quiet",
+            Queue::Digest(frequency) => return self.create_digest_message(rollup, frequency),
+        };
+        let text = format!("{}: {}", heading, rollup.summary());
+        let items = rollup
+            .entries
+            .iter()
+            .map(|entry| format!("{} ({} by {})", self.entry_text(entry), escape(&entry.action), escape(&entry.author)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Message {
+            blocks: vec![
+                Block::section(Text::mrkdwn(&format!("*{}:* {}", heading, rollup.summary()))),
+                Block::section(Text::mrkdwn(&items)),
+            ],
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text,
+        }
+    }
+
+    /// Issues, merged pull requests and releases since the previous digest, then who was most active.
+    pub fn create_digest_message(&self, rollup: &Rollup, frequency: Frequency) -> Message {
+        let digest = Digest::compile(&rollup.entries);
+        let heading = match frequency {
+            Frequency::Daily => "Daily digest",
+            Frequency::Weekly => "Weekly digest",
+        };
+        let mut blocks = vec![Block::header(heading)];
+
+        for (title, entries) in [
+            ("Issues opened", &digest.opened_issues),
+            ("Issues closed", &digest.closed_issues),
+            ("Pull requests merged", &digest.merged_pulls),
+            ("Releases", &digest.releases),
+        ] {
+            if !entries.is_empty() {
+                let items = entries.iter().map(|entry| self.entry_text(entry)).collect::<Vec<_>>().join("\n");
+                blocks.push(Block::section(Text::mrkdwn(&format!("*{} ({})*\n{}", title, entries.len(), items))));
+            }
+        }
+
+        if digest.is_empty() {
+            blocks.push(Block::section(Text::mrkdwn("No issues were opened or closed, and no pull requests merged or releases published.")));
+        }
+
+        if !digest.top_contributors.is_empty() {
+            let contributors = digest
+                .top_contributors
+                .iter()
+                .map(|(login, count)| format!("{} ({})", link(&format!("https://github.com/{}", login), login), count))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            blocks.push(Block::divider());
+            blocks.push(Block::context(vec![ContextElement::Text(Text::mrkdwn(&format!("Top contributors: {}", contributors)))]));
+        }
+
+        let text = format!(
+            "{}: {} issues opened, {} closed, {} pull requests merged, {} releases",
+            heading,
+            digest.opened_issues.len(),
+            digest.closed_issues.len(),
+            digest.merged_pulls.len(),
+            digest.releases.len(),
+        );
+
+        Message {
+            blocks,
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text,
+        }
+    }
+
+    pub fn create_release_message(&self, body: &GithubWebhookRequest, release: &Release) -> Message {
+        let name = release.name.as_deref().filter(|name| !name.is_empty()).unwrap_or(&release.tag_name);
+        let text = format!(":package: {} released {}", escape(&body.full_name()), link(&release.html_url, name));
+
+        Message {
+            blocks: vec![Block::section(Text::mrkdwn(&text))],
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text,
+        }
+    }
+
+    /// Failed deployments link to their logs when GitHub has them, advisories to their GitHub page.
+    pub fn create_alert_message(&self, alert: &AlertWebhookRequest) -> Option<Message> {
+        let repository = alert.full_name().unwrap_or_default();
+        let text = match (&alert.security_advisory, &alert.deployment_status) {
+            (Some(advisory), _) => format!(
+                ":rotating_light: {} security advisory {}",
+                escape(&advisory.severity),
+                link(&format!("https://github.com/advisories/{}", advisory.ghsa_id), &advisory.summary),
+            ),
+            (None, Some(status)) => {
+                let environment = match status.log_url.as_deref().or(status.target_url.as_deref()) {
+                    Some(url) => link(url, &status.environment),
+                    None => escape(&status.environment),
+                };
+                let name = alert.deployment.as_ref().map(|deployment| format!(" of `{}`", escape(&deployment.name))).unwrap_or_default();
+
+                format!(":rotating_light: Deployment{} to {} failed in {}", name, environment, escape(&repository))
+            },
+            (None, None) => return None,
+        };
+
+        Some(Message {
+            blocks: vec![Block::section(Text::mrkdwn(&text))],
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text,
+        })
+    }
+
+    /// Releases have no number, so they are named after the repository instead.
+    fn entry_text(&self, entry: &RollupEntry) -> String {
+        let name = match entry.number {
+            Some(number) => format!("{}#{}", entry.repository, number),
+            None => entry.repository.to_string(),
+        };
+
+        format!("• {} {}", link(&entry.html_url, &name), escape(&entry.title))
+    }
+
+    pub fn create_lookup_message<T: PayloadRepository>(&self, payload: &T, text: &str, reference: &IssueReference, labels: &[Label]) -> Message {
+        self.construct_card(payload, payload.card_state(), &self.text_lines(payload, text, ""), reference, labels)
+    }
+
+    pub fn text_lines<T: PayloadRepository>(&self, payload: &T, body: &str, prefix_text: &str) -> String {
+        let re = Regex::new(r"(?P<owner>[\w.-]*)\/(?P<repo>[\w.-]*)\#(?P<issue_number>\d*)").unwrap();
+
+        let mut to_string = String::new();
+
+        for caps in re.captures_iter(body) {
+            to_string = format!("{}/{}#{}", &caps["owner"], &caps["repo"], &caps["issue_number"]);
+        }
+
+        let text_line = TextLine {
+            title: format!("*{} - {}*", escape(payload.title()), link(payload.html_url(), &to_string)),
+            state: format!(
+                "*{}* - Created by {} on {}",
+                payload.state(),
+                user_link(&payload.user().html_url, &payload.user().login),
+                timestamp(payload.created_at()),
+            ),
+        };
+
+        let body = truncate(&from_markdown(payload.body().unwrap_or("")), MAX_BODY_LENGTH, payload.html_url());
+
+        [
+            prefix_text,
+            text_line.title.as_str(),
+            body.as_str(),
+            text_line.state.as_str(),
+        ]
+        .into_iter()
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+
+    pub fn construct_message<T: PayloadRepository>(&self, payload: &T, text_lines: &str) -> Vec<Block> {
+        vec![
+            Block::section(Text::mrkdwn(text_lines))
+                .with_accessory(Element::image(&payload.user().avatar_url, &payload.user().login))
+        ]
+    }
+
+    pub fn construct_actions<T: PayloadRepository>(&self, payload: &T, reference: &IssueReference, labels: &[Label]) -> Block {
+        let value = reference.to_string();
+
+        let mut elements = vec![
+            if payload.state() == State::Open {
+                Element::button("close", "Close", &value).with_style(ButtonStyle::Danger)
+            } else {
+                Element::button("reopen", "Reopen", &value)
+            },
+            Element::button("assign_to_me", "Assign to me", &value),
+        ];
+
+        // Slack accepts at most 100 options in a static select. A shortened value would add another
+        // label, so labels whose value does not fit are left out instead.
+        let options = labels
+            .iter()
+            .map(|label| OptionObject::new(&label.name, &format!("{}:{}", value, label.name)))
+            .filter(|option| option.value.chars().count() <= MAX_OPTION_VALUE)
+            .take(100)
+            .collect::<Vec<_>>();
+
+        if !options.is_empty() {
+            elements.push(Element::static_select("add_label", "Add label", options));
+        }
+
+        elements.push(Element::link_button("open_in_github", "Open in GitHub", payload.html_url()));
+
+        Block::actions(elements)
+    }
+
+    /// Title and body for a GitHub issue created from a Slack message, within Slack's input limits.
+    pub fn issue_draft(&self, text: &str, author: Option<&SlackUser>, permalink: &str) -> (String, String) {
+        let title = text
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("")
+            .trim()
+            .chars()
+            .take(100)
+            .collect::<String>();
+
+        let author = author
+            .map(|user| user.real_name.as_deref().unwrap_or(&user.name).to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let footer = format!("\n\n---\nReported by {} in Slack: {}", author, permalink);
+
+        let body = text
+            .chars()
+            .take(3000usize.saturating_sub(footer.chars().count()))
+            .chain(footer.chars())
+            .collect::<String>();
+
+        (title, body)
+    }
+
+    pub fn construct_create_issue_view(&self, title: &str, body: &str, repositories: &[Repository], labels: &[Label], origin: &ShortcutOrigin) -> View {
+        let mut blocks = vec![
+            Block::input(
+                "Repository",
+                Element::static_select(
+                    "select_repository",
+                    "Select a repository",
+                    repositories
+                        .iter()
+                        .take(100)
+                        .map(|repository| OptionObject::new(&repository.full_name, &repository.full_name))
+                        .collect(),
+                ),
+            )
+            .with_block_id("repository")
+            .dispatch_action(),
+        ];
+
+        if !labels.is_empty() {
+            blocks.push(
+                Block::input(
+                    "Labels",
+                    Element::multi_static_select(
+                        "labels",
+                        "Select labels",
+                        labels
+                            .iter()
+                            .take(100)
+                            .map(|label| OptionObject::new(&label.name, &label.name))
+                            .collect(),
+                    ),
+                )
+                .with_block_id("labels")
+                .optional()
+            );
+        }
+
+        blocks.push(Block::input("Title", Element::plain_text_input("title").with_initial_value(title)).with_block_id("title"));
+        blocks.push(Block::input("Description", Element::plain_text_input("body").with_initial_value(body).multiline()).with_block_id("body"));
+
+        View {
+            r#type: "modal".to_string(),
+            callback_id: Some("create_issue".to_string()),
+            title: Some(Text::plain("Create issue")),
+            submit: Some(Text::plain("Create")),
+            private_metadata: Some(serde_json::json!(origin).to_string()),
+            blocks,
+        }
+    }
+
+    /// A modal with nothing but `text`, opened while the real one is prepared or shown instead of it.
+    pub fn construct_notice_view(&self, text: &str) -> View {
+        View {
+            r#type: "modal".to_string(),
+            callback_id: None,
+            title: Some(Text::plain("Create issue")),
+            submit: None,
+            private_metadata: None,
+            blocks: vec![Block::section(Text::mrkdwn(text))],
+        }
+    }
+
+    pub fn construct_home_view(&self, login: Option<&str>, assigned: &[SearchItem], review_requests: &[SearchItem], pull_requests: &[(SearchItem, CiStatus)]) -> View {
+        let login = match login {
+            Some(login) => login,
+            None => {
+                return HomeViewBuilder::default()
+                    .header("Your GitHub work")
+                    .section("Your Slack account is not linked to a GitHub account yet.")
+                    .build();
+            },
+        };
+
+        let list = |items: Vec<String>| {
+            if items.is_empty() {
+                "_Nothing here_ :tada:".to_string()
+            } else {
+                items.join("\n")
+            }
+        };
+
+        let item_line = |item: &SearchItem| format!("• {} {}", link(&item.html_url, &item.reference().to_string()), escape(&item.title));
+
+        HomeViewBuilder::default()
+            .header(&format!("Your GitHub work ({})", login))
+            .divider()
+            .section("*Assigned issues*")
+            .section(&list(assigned.iter().map(item_line).collect()))
+            .divider()
+            .section("*Pull requests awaiting your review*")
+            .section(&list(review_requests.iter().map(item_line).collect()))
+            .divider()
+            .section("*Your open pull requests*")
+            .section(&list(pull_requests.iter().map(|(item, status)| format!("{} {}", status.emoji(), item_line(item))).collect()))
+            .build()
+    }
+
+    pub fn auto_expand_text(&self, enabled: bool) -> String {
+        format!("Issue references in this channel will {}be expanded", if enabled { "" } else { "no longer " })
+    }
+
+    pub fn direct_messages_text(&self, enabled: bool) -> String {
+        format!("You will {}get direct messages about reviews, assignments and mentions", if enabled { "" } else { "no longer " })
+    }
+
+    pub fn route_text(&self, scope: &RouteScope, routed: bool) -> String {
+        format!("This channel will {}receive {}", if routed { "" } else { "no longer " }, self.route_events(scope))
+    }
+
+    pub fn share_text(&self, repository: &str, shared: bool) -> String {
+        format!("{} is {}shared with this channel", repository, if shared { "" } else { "no longer " })
+    }
+
+    pub fn not_routed_text(&self, scope: &RouteScope) -> String {
+        format!("This channel does not receive {} through a route", self.route_events(scope))
+    }
+
+    fn route_events(&self, scope: &RouteScope) -> String {
+        match scope {
+            RouteScope::Default => "events no route matches".to_string(),
+            RouteScope::Repository { repository, event: None } => format!("events of {}", repository),
+            RouteScope::Repository { repository, event: Some(event) } => format!("`{}` events of {}", event, repository),
+        }
+    }
+
+    pub fn routing_table_text(&self, table: &RoutingTable) -> String {
+        let channels = |channels: &[String]| channels.iter().map(|channel| format!("<#{}>", channel)).collect::<Vec<_>>().join(" ");
+
+        let mut lines: Vec<String> = table
+            .routes
+            .iter()
+            .map(|route| match &route.event {
+                Some(event) => format!("• {} `{}` → {}", route.repository, event, channels(&route.channels)),
+                None => format!("• {} → {}", route.repository, channels(&route.channels)),
+            })
+            .collect();
+
+        if let Some(channel) = &table.default_channel {
+            lines.push(format!("Everything else → <#{}>", channel));
+        }
+
+        if lines.is_empty() {
+            return "No routes yet, all events go to the default channel".to_string();
+        }
+
+        lines.join("\n")
+    }
+
+    pub fn subscription_text(&self, repository: &str, subscription: Option<&Subscription>) -> String {
+        match subscription {
+            Some(subscription) => format!(
+                "This channel receives {} of {}{}{}",
+                self.categories_text(subscription),
+                repository,
+                self.filter_text(subscription),
+                self.digest_text(subscription),
+            ),
+            None => format!("This channel no longer receives events of {}", repository),
+        }
+    }
+
+    pub fn subscriptions_text(&self, subscriptions: &ChannelSubscriptions) -> String {
+        if subscriptions.subscriptions.is_empty() {
+            return "This channel has no subscriptions yet".to_string();
+        }
+
+        subscriptions
+            .subscriptions
+            .iter()
+            .map(|subscription| format!(
+                "• {}: {}{}{}",
+                subscription.repository,
+                self.categories_text(subscription),
+                self.filter_text(subscription),
+                self.digest_text(subscription),
+            ))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn categories_text(&self, subscription: &Subscription) -> String {
+        subscription.categories.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    }
+
+    fn digest_text(&self, subscription: &Subscription) -> String {
+        match subscription.digest {
+            Some(frequency) => format!(" in a {} digest", frequency),
+            None => String::new(),
+        }
+    }
+
+    fn filter_text(&self, subscription: &Subscription) -> String {
+        match subscription.filter.rules.as_slice() {
+            [] => String::new(),
+            rules => format!(" matching {}", rules.iter().map(|rule| format!("`{}`", escape(&rule.to_string()))).collect::<Vec<_>>().join(" ")),
+        }
+    }
+
+    pub fn quiet_hours_text(&self, quiet_hours: Option<&QuietHours>) -> String {
+        match quiet_hours {
+            Some(quiet_hours) => format!(
+                "Between {} and {} ({}) this channel only receives urgent events; the rest follows as a catch-up summary",
+                quiet_hours.start.format("%H:%M"),
+                quiet_hours.end.format("%H:%M"),
+                quiet_hours.timezone,
+            ),
+            None => "Quiet hours are off, this channel receives events as they happen".to_string(),
+        }
+    }
+
+    pub fn reference_summary(&self, issues: &[(IssueReference, Issue)]) -> String {
+        issues
+            .iter()
+            .map(|(reference, issue)| format!("• {} {} ({})", link(&issue.html_url, &reference.to_string()), escape(&issue.title), issue.state))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn construct_commit_message(&self, commit: &Commit, owner: &str, repo: &str) -> Vec<Block> {
+        let (author, avatar_url) = match &commit.author {
+            Some(user) => (user_link(&user.html_url, &user.login), user.avatar_url.to_string()),
+            None => (escape(&commit.commit.author.name), "https://github.com/ghost.png".to_string()),
+        };
+
+        let text_lines = [
+            format!(
+                "*{} - {}*",
+                escape(commit.commit.message.lines().next().unwrap_or("")),
+                link(&commit.html_url, &format!("{}/{}@{}", owner, repo, commit.sha.get(..7).unwrap_or(&commit.sha))),
+            ),
+            format!(
+                "Committed by {} on {}",
+                author,
+                timestamp(&commit.commit.author.date),
+            ),
+        ]
+        .join("\n");
+
+        vec![
+            Block::section(Text::mrkdwn(&text_lines))
+                .with_accessory(Element::image(&avatar_url, &commit.commit.author.name))
+        ]
+    }
+
+    /// Posts the first page of `message` and the rest into its thread; returns the first page.
+    pub async fn post_message(&self, bot_token: &str, channel: &str, thread_ts: Option<&str>, message: Message) -> Result<PostedMessage, Box<dyn Error>> {
+        let mut pages = message.pages().into_iter();
+        let first = pages.next().ok_or("message has no content")?;
+
+        let posted = self.post_page(bot_token, channel, thread_ts, first, None).await?;
+
+        for page in pages {
+            self.post_page(bot_token, channel, Some(thread_ts.unwrap_or(&posted.ts)), page, None).await?;
+        }
+
+        Ok(posted)
+    }
+
+    /// Replies in the thread of `parent`; broadcast replies are also shown in the channel.
+    /// Only the first page of an oversized reply is broadcast.
+    pub async fn post_reply(&self, bot_token: &str, parent: &PostedMessage, message: Message, broadcast: bool) -> Result<PostedMessage, Box<dyn Error>> {
+        let mut pages = message.pages().into_iter();
+        let first = pages.next().ok_or("message has no content")?;
+
+        let posted = self.post_page(bot_token, &parent.channel, Some(&parent.ts), first, Some(broadcast)).await?;
+
+        for page in pages {
+            self.post_page(bot_token, &parent.channel, Some(&parent.ts), page, None).await?;
+        }
+
+        Ok(posted)
+    }
+
+    async fn post_page(&self, bot_token: &str, channel: &str, thread_ts: Option<&str>, page: Message, reply_broadcast: Option<bool>) -> Result<PostedMessage, Box<dyn Error>> {
+        self.validate_page(&page)?;
+
+        let request = PostMessageRequest {
+            channel: channel.to_string(),
+            thread_ts: thread_ts.map(str::to_string),
+            blocks: page.blocks,
+            attachments: page.attachments,
+            text: Some(page.text),
+            reply_broadcast,
+        };
+
+        self.call_api(bot_token, "chat.postMessage", &request).await
+    }
+
+    fn validate_page(&self, page: &Message) -> Result<(), String> {
+        validate_blocks(&page.blocks, MAX_MESSAGE_BLOCKS)?;
+
+        page.attachments
+            .iter()
+            .try_for_each(|attachment| validate_blocks(&attachment.blocks, MAX_MESSAGE_BLOCKS))
+    }
+
+    pub async fn post_text(&self, bot_token: &str, channel: &str, thread_ts: Option<&str>, text: &str) -> Result<PostedMessage, Box<dyn Error>> {
+        let request = PostMessageRequest {
+            channel: channel.to_string(),
+            thread_ts: thread_ts.map(str::to_string),
+            blocks: vec![],
+            attachments: vec![],
+            text: Some(text.to_string()),
+            reply_broadcast: None,
+        };
+
+        self.call_api(bot_token, "chat.postMessage", &request).await
+    }
+
+    /// A posted message cannot grow a thread of its own, so only the first page of `message` is kept.
+    pub async fn update_message(&self, bot_token: &str, posted: &PostedMessage, message: Message) -> Result<(), Box<dyn Error>> {
+        let page = message.pages().into_iter().next().ok_or("message has no content")?;
+
+        self.validate_page(&page)?;
+
+        let request = UpdateMessageRequest {
+            channel: posted.channel.to_string(),
+            ts: posted.ts.to_string(),
+            blocks: page.blocks,
+            attachments: page.attachments,
+            text: page.text,
+        };
+
+        self.call_api::<_, ApiResponse>(bot_token, "chat.update", &request).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_message(&self, bot_token: &str, posted: &PostedMessage) -> Result<(), Box<dyn Error>> {
+        self.call_api::<_, ApiResponse>(bot_token, "chat.delete", posted).await?;
+
+        Ok(())
+    }
+
+    pub async fn add_reaction(&self, bot_token: &str, posted: &PostedMessage, name: &str) -> Result<(), Box<dyn Error>> {
+        match self.call_api::<_, ApiResponse>(bot_token, "reactions.add", &self.reaction_request(posted, name)).await {
+            Err(e) if e.to_string().ends_with("already_reacted") => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    pub async fn remove_reaction(&self, bot_token: &str, posted: &PostedMessage, name: &str) -> Result<(), Box<dyn Error>> {
+        match self.call_api::<_, ApiResponse>(bot_token, "reactions.remove", &self.reaction_request(posted, name)).await {
+            Err(e) if e.to_string().ends_with("no_reaction") => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn reaction_request(&self, posted: &PostedMessage, name: &str) -> ReactionRequest {
+        ReactionRequest {
+            channel: posted.channel.to_string(),
+            timestamp: posted.ts.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Trigger IDs expire after 3 seconds, so slow modals are opened with a placeholder and updated through the returned view.
+    pub async fn open_view(&self, bot_token: &str, trigger_id: &str, view: View) -> Result<OpenedView, Box<dyn Error>> {
+        validate_blocks(&view.blocks, MAX_VIEW_BLOCKS)?;
+
+        let request = ViewsOpenRequest {
+            trigger_id: trigger_id.to_string(),
+            view,
+        };
+
+        let res = self.call_api::<_, ViewsOpenResponse>(bot_token, "views.open", &request).await?;
+
+        Ok(res.view)
+    }
+
+    pub async fn publish_view(&self, bot_token: &str, user_id: &str, view: View) -> Result<(), Box<dyn Error>> {
+        validate_blocks(&view.blocks, MAX_VIEW_BLOCKS)?;
+
+        let request = ViewsPublishRequest {
+            user_id: user_id.to_string(),
+            view,
+        };
+
+        self.call_api::<_, ApiResponse>(bot_token, "views.publish", &request).await?;
+
+        Ok(())
+    }
+
+    pub async fn update_view(&self, bot_token: &str, view_id: &str, hash: &str, view: View) -> Result<(), Box<dyn Error>> {
+        validate_blocks(&view.blocks, MAX_VIEW_BLOCKS)?;
+
+        let request = ViewsUpdateRequest {
+            view_id: view_id.to_string(),
+            hash: hash.to_string(),
+            view,
+        };
+
+        self.call_api::<_, ApiResponse>(bot_token, "views.update", &request).await?;
+
+        Ok(())
+    }
+
+    pub async fn fetch_permalink(&self, bot_token: &str, channel: &str, message_ts: &str) -> Result<String, Box<dyn Error>> {
+        let res = self
+            .call_form_api::<PermalinkResponse>(bot_token, "chat.getPermalink", &[("channel", channel), ("message_ts", message_ts)])
+            .await?;
+
+        Ok(res.permalink)
+    }
+
+    /// Opens (or reuses) the direct message channel between the bot and `user`.
+    pub async fn open_conversation(&self, bot_token: &str, user: &str) -> Result<String, Box<dyn Error>> {
+        let res = self
+            .call_form_api::<ConversationsOpenResponse>(bot_token, "conversations.open", &[("users", user)])
+            .await?;
+
+        Ok(res.channel.id)
+    }
+
+    pub async fn fetch_user(&self, bot_token: &str, user: &str) -> Result<SlackUser, Box<dyn Error>> {
+        let res = self
+            .call_form_api::<UserInfoResponse>(bot_token, "users.info", &[("user", user)])
+            .await?;
+
+        Ok(res.user)
+    }
+
+    pub async fn oauth_access(&self, client_id: &str, client_secret: &str, code: &str, redirect_uri: &str) -> Result<OAuthAccessResponse, Box<dyn Error>> {
+        let client = reqwest::Client::new();
+
+        let res = client
+            .post("https://slack.com/api/oauth.v2.access")
+            .basic_auth(client_id, Some(client_secret))
+            .form(&[("code", code), ("redirect_uri", redirect_uri)])
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
+
+        self.parse_api_response("oauth.v2.access", res)
+    }
+
+    pub async fn respond(&self, response_url: &str, message: &ResponseUrlMessage) -> Result<(), Box<dyn Error>> {
+        validate_blocks(&message.blocks, MAX_MESSAGE_BLOCKS)?;
+
+        let client = reqwest::Client::new();
+
+        client
+            .post(response_url)
+            .json(message)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn unfurl(&self, bot_token: &str, channel: &str, ts: &str, unfurls: HashMap<String, Unfurl>) -> Result<(), Box<dyn Error>> {
+        for unfurl in unfurls.values() {
+            validate_blocks(&unfurl.blocks, MAX_MESSAGE_BLOCKS)?;
+        }
+
+        let request = UnfurlRequest {
+            channel: channel.to_string(),
+            ts: ts.to_string(),
+            unfurls,
+        };
+
+        self.call_api::<_, ApiResponse>(bot_token, "chat.unfurl", &request).await?;
+
+        Ok(())
+    }
+
+    async fn call_api<B: Serialize, R: DeserializeOwned>(&self, bot_token: &str, method: &str, body: &B) -> Result<R, Box<dyn Error>> {
+        let client = reqwest::Client::new();
+
+        let res = client
+            .post(format!("https://slack.com/api/{}", method))
+            .bearer_auth(bot_token)
+            .json(body)
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
+
+        self.parse_api_response(method, res)
+    }
+
+    // Read methods such as users.info do not accept JSON bodies.
+    async fn call_form_api<R: DeserializeOwned>(&self, bot_token: &str, method: &str, params: &[(&str, &str)]) -> Result<R, Box<dyn Error>> {
+        let client = reqwest::Client::new();
+
+        let res = client
+            .post(format!("https://slack.com/api/{}", method))
+            .bearer_auth(bot_token)
+            .form(params)
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
+
+        self.parse_api_response(method, res)
+    }
+
+    fn parse_api_response<R: DeserializeOwned>(&self, method: &str, res: serde_json::Value) -> Result<R, Box<dyn Error>> {
+        let status: ApiResponse = serde_json::from_value(res.clone())?;
+
+        if !status.ok {
+            return Err(format!("{} failed: {}", method, status.error.unwrap_or_default()).into());
+        }
+
+        Ok(serde_json::from_value(res)?)
+    }
+
+    /// Incoming webhooks cannot thread, so the pages of an oversized message follow one another.
+    pub async fn send_message(&self, slack_webhook_url: &str, message: Message) -> Result<(), Box<dyn Error>> {
+        let client = reqwest::Client::new();
+
+        for page in message.pages() {
+            self.validate_page(&page)?;
+
+            client
+                .post(slack_webhook_url)
+                .json(&page)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn verify_signature(&self, signing_secret: &str, timestamp: &str, body: &str, signature: &str, now: u64) -> bool {
+        // Slack recommends rejecting requests older than five minutes to prevent replay attacks.
+        let sent_at = match timestamp.parse::<u64>() {
+            Ok(sent_at) => sent_at,
+            Err(_) => return false,
+        };
+
+        if now.abs_diff(sent_at) > 60 * 5 {
+            return false;
+        }
+
+        let signature = match signature.strip_prefix("v0=").map(hex::decode) {
+            Some(Ok(signature)) => signature,
+            _ => return false,
+        };
+
+        let mut mac = match Hmac::<Sha256>::new_from_slice(signing_secret.as_bytes()) {
+            Ok(mac) => mac,
+            Err(_) => return false,
+        };
+
+        mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    /// OAuth `state` parameter: the issue time signed with the client secret, so no server-side storage is needed.
+    pub fn oauth_state(&self, client_secret: &str, now: u64) -> String {
+        format!("{}.{}", now, self.sign(client_secret, &now.to_string()))
+    }
+
+    pub fn verify_oauth_state(&self, client_secret: &str, state: &str, now: u64) -> bool {
+        let (issued_at, signature) = match state.split_once('.') {
+            Some((issued_at, signature)) => (issued_at, signature),
+            None => return false,
+        };
+
+        self.is_fresh(issued_at, now) && self.verify_hex_signature(client_secret, issued_at, signature)
+    }
+
+    /// State for the GitHub account linking flow: who asked to link which login, signed with the GitHub client secret.
+    pub fn link_state(&self, client_secret: &str, team_id: &str, user_id: &str, login: &str, now: u64) -> String {
+        let data = format!("{}.{}.{}.{}", team_id, user_id, login, now);
+
+        format!("{}.{}", data, self.sign(client_secret, &data))
+    }
+
+    /// Returns the team, user and login a valid link state was issued for.
+    pub fn verify_link_state(&self, client_secret: &str, state: &str, now: u64) -> Option<(String, String, String)> {
+        let (data, signature) = state.rsplit_once('.')?;
+
+        match data.split('.').collect::<Vec<_>>()[..] {
+            [team_id, user_id, login, issued_at] if self.is_fresh(issued_at, now) && self.verify_hex_signature(client_secret, data, signature) => {
+                Some((team_id.to_string(), user_id.to_string(), login.to_string()))
+            },
+            _ => None,
+        }
+    }
+
+    fn sign(&self, secret: &str, data: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+
+        mac.update(data.as_bytes());
+
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn is_fresh(&self, issued_at: &str, now: u64) -> bool {
+        matches!(issued_at.parse::<u64>(), Ok(issued_at) if issued_at <= now && now - issued_at <= 60 * 10)
+    }
+
+    fn verify_hex_signature(&self, secret: &str, data: &str, signature: &str) -> bool {
+        let signature = match hex::decode(signature) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+
+        let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+            Ok(mac) => mac,
+            Err(_) => return false,
+        };
+
+        mac.update(data.as_bytes());
+
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    pub fn link_text(&self, authorize_url: &str) -> String {
+        format!("<{}|Sign in with GitHub> to link your account. The link is valid for 10 minutes.", authorize_url)
+    }
+}
+
+/// GitHub timestamps as `2024-07-07 20:09:31`, or as sent when they are in another format.
+fn timestamp(value: &str) -> String {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%SZ")
+        .map(|time| time.to_string())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+#[cfg(test)]
+mod message_test {
+    use super::*;
+    use crate::domain::model::{
+        filter::Rule,
+        github::{Commit, Issue, Label, PullRequest, SearchItem, User},
+        rollup::{ItemKind, Queue, RollupEntry},
+        slack::{MessageSink, SlackUserProfile},
+        subscription::Category,
+    };
+
+    #[test]
+    fn test_issue_text_lines() {
+        let issue = Issue {
+            html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
+            title: "test".to_string(),
+            body: Some("body".to_string()),
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 1,
+            user: User {
+                html_url: "https://github.com/signalnerve".to_string(),
+                login: "test".to_string(),
+                avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
+            },
+            pull_request: None,
+        };
+
+        assert_eq!(
+            "*test - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\nbody\n*open* - Created by <https://github.com/signalnerve|test\u{2063}> on 2024-07-07 20:09:31".to_string(),
+            Slack.text_lines(
+                &issue,
+                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
+                ""
+            )
+        );
+    }
+
+    #[test]
+    fn test_issue_text_lines_is_body_none() {
+        let issue = Issue {
+            html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
+            title: "test".to_string(),
+            body: None,
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 1,
+            user: User {
+                html_url: "https://github.com/signalnerve".to_string(),
+                login: "test".to_string(),
+                avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
+            },
+            pull_request: None,
+        };
+
+        assert_eq!(
+            "*test - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\n*open* - Created by <https://github.com/signalnerve|test\u{2063}> on 2024-07-07 20:09:31".to_string(),
+            Slack.text_lines(
+                &issue,
+                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
+                ""
+            )
+        );
+    }
+
+    #[test]
+    fn test_pull_request_text_lines() {
+        let pull_request = PullRequest {
+            html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
+            title: "test pull_request".to_string(),
+            body: Some("body pull_request".to_string()),
+            state: State::Open,
+            draft: false,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 2,
+            user: User {
+                html_url: "https://github.com/reo0306".to_string(),
+                login: "test2".to_string(),
+                avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
+            },
+            merged: false,
+            merged_by: None,
+            base: None,
+        };
+
+        assert_eq!(
+            "*test pull_request - <https://github.com/reo0306/rust-todo-di-app/pull/1|>*\nbody pull_request\n*open* - Created by <https://github.com/reo0306|test2\u{2063}> on 2024-07-07 20:09:31".to_string(),
+            Slack.text_lines(
+                &pull_request,
+                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
+                ""
+            )
+        );
+    }
+
+    #[test]
+    fn test_pull_request_text_lines_is_body_none() {
+        let pull_request = PullRequest {
+            html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
+            title: "test pull_request".to_string(),
+            body: None,
+            state: State::Open,
+            draft: false,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 2,
+            user: User {
+                html_url: "https://github.com/reo0306".to_string(),
+                login: "test2".to_string(),
+                avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
+            },
+            merged: false,
+            merged_by: None,
+            base: None,
+        };
+
+        assert_eq!(
+            "*test pull_request - <https://github.com/reo0306/rust-todo-di-app/pull/1|>*\n*open* - Created by <https://github.com/reo0306|test2\u{2063}> on 2024-07-07 20:09:31".to_string(),
+            Slack.text_lines(
+                &pull_request,
+                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
+                ""
+            )
+        );
+    }
+
+    #[test]
+    fn test_construct_message() {
+        let issue = Issue {
+            html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
+            title: "test".to_string(),
+            body: Some("body".to_string()),
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 1,
+            user: User {
+                html_url: "https://github.com/signalnerve".to_string(),
+                login: "test".to_string(),
+                avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
+            },
+            pull_request: None,
+        };
+
+        let text_lines = Slack.text_lines(
+            &issue,
+            "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
+            ""
+        );
+
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn(&text_lines))
+                    .with_accessory(Element::image(&issue.user.avatar_url, &issue.user.login))
+            ],
+            Slack.construct_message(&issue, &text_lines)
+        );
+    }
+
+    #[test]
+    fn test_create_card_merged() {
+        let merger = User {
+            html_url: "https://github.com/octocat".to_string(),
+            login: "octocat".to_string(),
+            avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
+        };
+        let pull_request = PullRequest {
+            html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
+            title: "test pull_request".to_string(),
+            body: None,
+            state: State::Closed,
+            draft: false,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 1,
+            user: merger.clone(),
+            merged: true,
+            merged_by: Some(merger),
+            base: None,
+        };
+
+        let reference = IssueReference {
+            owner: "reo0306".to_string(),
+            repo: "rust-todo-di-app".to_string(),
+            number: 1,
+        };
+
+        let card = Slack.create_card(&pull_request, &reference, &[]);
+
+        assert_eq!(
+            Some(":tada: Merged by <https://github.com/octocat|octocat\u{2063}>\n*test pull_request - <https://github.com/reo0306/rust-todo-di-app/pull/1|reo0306/rust-todo-di-app#1>*\n*closed* - Created by <https://github.com/octocat|octocat\u{2063}> on 2024-07-07 20:09:31".to_string()),
+            card.attachments[0].blocks[0].text().map(str::to_string)
         );
+        assert_eq!(Some(":tada: Merged"), card.blocks[0].text());
+        assert_eq!("#8250df", card.attachments[0].color);
 
-        Message {
-            blocks: self.construct_message(payload, &text_lines),
-            response_type: "in_channel".to_string(),
-        }
+        assert_eq!("reo0306/rust-todo-di-app#1 test pull_request", card.text);
+
+        let unmerged = PullRequest { merged: false, ..pull_request };
+
+        assert_eq!(None, Slack.card_status(&unmerged));
     }
 
-    pub fn text_lines<T: PayloadRepository>(&self, payload: &T, body: &str, prefix_text: &str) -> String {
-        let re = Regex::new(r"(?P<owner>[\w.-]*)\/(?P<repo>[\w.-]*)\#(?P<issue_number>\d*)").unwrap();
+    #[test]
+    fn test_message_pages() {
+        let message = Message {
+            blocks: vec![Block::section(Text::mrkdwn(&"long line of text\n".repeat(500)))],
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text: "fallback".to_string(),
+        };
 
-        let mut to_string = String::new();
+        let pages = message.pages();
 
-        for caps in re.captures_iter(body) {
-            to_string = format!("{}/{}#{}", &caps["owner"], &caps["repo"], &caps["issue_number"]);
-        }
+        assert_eq!(1, pages.len());
+        assert_eq!(4, pages[0].blocks.len());
+        assert!(validate_blocks(&pages[0].blocks, MAX_MESSAGE_BLOCKS).is_ok());
 
-        let text_line = TextLine {
-            title: format!("*{} - <{}|{}>*", payload.title(), payload.html_url(), to_string),
-            state: format!(
-                "*{}* - Created by <{}|{}> on {}",
-                payload.state(),
-                payload.user().html_url,
-                payload.user().login,
-                NaiveDateTime::parse_from_str(payload.created_at(), "%Y-%m-%dT%H:%M:%SZ").unwrap(),
-            ),
+        let message = Message {
+            blocks: (0..120).map(|_| Block::divider()).collect(),
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text: "fallback".to_string(),
         };
 
-	[
-            prefix_text,
-            text_line.title.as_str(),
-            payload.body().unwrap_or(""),
-            text_line.state.as_str(),
-        ]
-        .into_iter()
-        .filter(|t| !t.is_empty())
-        .collect::<Vec<_>>()
-        .join("\n")
+        assert_eq!(vec![50, 50, 20], message.pages().iter().map(|page| page.blocks.len()).collect::<Vec<_>>());
+
+        let message = Message {
+            blocks: (0..20).map(|_| Block::section(Text::mrkdwn(&"a".repeat(2900)))).collect(),
+            attachments: vec![],
+            response_type: "in_channel".to_string(),
+            text: "fallback".to_string(),
+        };
+        let pages = message.pages();
+
+        assert_eq!(2, pages.len());
+        assert!(pages.iter().all(|page| serde_json::to_string(&page.blocks).unwrap().len() <= 40_000));
+        assert!(pages.iter().all(|page| page.text == "fallback"));
     }
 
-    pub fn construct_message<T: PayloadRepository>(&self, payload: &T, text_lines: &String) -> Vec<Blocks> {
-        vec![
-            Blocks {
-                r#type: "section".to_string(),
-                text: Text {
-                    r#type: "mrkdwn".to_string(),
-                    text: text_lines.to_string(),
-                },
-                accessory: Accessory {
-                    r#type: "image".to_string(),
-                    image_url: payload.user().avatar_url.to_string(),
-                    alt_text: payload.user().login.to_string(),
-                },
-            }
-        ]
+    #[test]
+    fn test_long_body_card() {
+        let issue = Issue {
+            html_url: "https://github.com/o/r/issues/1".to_string(),
+            title: "t".to_string(),
+            body: Some("a long line of the issue body\n".repeat(400)),
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 1,
+            user: User {
+                html_url: "https://github.com/u".to_string(),
+                login: "u".to_string(),
+                avatar_url: "https://github.com/u.png".to_string(),
+            },
+            pull_request: None,
+        };
+        let reference = IssueReference { owner: "o".to_string(), repo: "r".to_string(), number: 1 };
+
+        let pages = Slack.create_lookup_message(&issue, "o/r#1", &reference, &[]).pages();
+
+        assert_eq!(1, pages.len());
+        assert!(pages[0].attachments[0].blocks.len() > 2);
+        assert!(Slack.validate_page(&pages[0]).is_ok());
     }
 
-    pub async fn send_message(&self, slack_webhook_url: &str, message : &Message) -> Result<(), Box::<dyn Error>> {
-        let client = reqwest::Client::new();
+    #[test]
+    fn test_message_pages_attachments() {
+        let message = Message {
+            blocks: vec![Block::header("Card")],
+            attachments: vec![Attachment::new("#2eb886", vec![Block::section(Text::mrkdwn(&"long line of text\n".repeat(500)))])],
+            response_type: "in_channel".to_string(),
+            text: "fallback".to_string(),
+        };
 
-        let message = serde_json::json!(message);
+        let pages = message.pages();
 
-        client
-            .post(slack_webhook_url)
-            .json(&message)
-            .send()
-            .await?;
+        assert_eq!(1, pages.len());
+        assert_eq!(4, pages[0].attachments[0].blocks.len());
+        assert!(Slack.validate_page(&pages[0]).is_ok());
 
-        Ok(())
+        let message = Message {
+            blocks: vec![],
+            attachments: vec![Attachment::new("#2eb886", (0..60).map(|_| Block::divider()).collect())],
+            response_type: "in_channel".to_string(),
+            text: "fallback".to_string(),
+        };
+
+        let pages = message.pages();
+
+        assert_eq!(50, pages[0].attachments[0].blocks.len());
+        assert_eq!(10, pages[0].blocks.len());
     }
-}
 
-#[cfg(test)]
-mod message_test {
-    use super::*;
-    use crate::domain::model::{
-        github::{Issue, PullRequest, User},
-        slack::{Blocks, Text, Accessory}
-    };
+    #[test]
+    fn test_construct_labels() {
+        let labels = vec![
+            Label { name: "bug".to_string(), color: "d73a4a".to_string() },
+            Label { name: "<docs>".to_string(), color: "0075ca".to_string() },
+        ];
+
+        assert_eq!(
+            serde_json::json!({
+                "type": "context",
+                "elements": [{"type": "mrkdwn", "text": ":red_circle: bug   :large_blue_circle: &lt;docs&gt;"}]
+            }),
+            serde_json::json!(Slack.construct_labels(&labels))
+        );
+    }
 
     #[test]
-    fn test_issue_text_lines() {
+    fn test_construct_actions() {
         let issue = Issue {
             html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
             title: "test".to_string(),
-            body: Some("body".to_string()),
-            state: "open".to_string(),
+            body: None,
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
             created_at: "2024-07-07T20:09:31Z".to_string(),
             number: 1,
             user: User {
                 html_url: "https://github.com/signalnerve".to_string(),
                 login: "test".to_string(),
                 avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-            }
+            },
+            pull_request: None,
         };
 
+        let reference = IssueReference {
+            owner: "cloudflare".to_string(),
+            repo: "wrangler-legacy".to_string(),
+            number: 1,
+        };
+
+        let labels = vec![
+            Label { name: "bug".to_string(), color: "d73a4a".to_string() },
+            Label { name: "x".repeat(150), color: "ededed".to_string() },
+        ];
+
         assert_eq!(
-            "*test - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\nbody\n*open* - Created by <https://github.com/signalnerve|test> on 2024-07-07 20:09:31".to_string(),
-            Slack.text_lines(
-                &issue,
-                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
-                ""
-            )
+            serde_json::json!({
+                "type": "actions",
+                "elements": [
+                    {"type": "button", "action_id": "close", "text": {"type": "plain_text", "text": "Close"}, "value": "cloudflare/wrangler-legacy#1", "style": "danger"},
+                    {"type": "button", "action_id": "assign_to_me", "text": {"type": "plain_text", "text": "Assign to me"}, "value": "cloudflare/wrangler-legacy#1"},
+                    {"type": "static_select", "action_id": "add_label", "placeholder": {"type": "plain_text", "text": "Add label"}, "options": [
+                        {"text": {"type": "plain_text", "text": "bug"}, "value": "cloudflare/wrangler-legacy#1:bug"}
+                    ]},
+                    {"type": "button", "action_id": "open_in_github", "text": {"type": "plain_text", "text": "Open in GitHub"}, "url": "https://github.com/cloudflare/wrangler-legacy/issues/1"}
+                ]
+            }),
+            serde_json::json!(Slack.construct_actions(&issue, &reference, &labels))
+        );
+
+        let closed = Issue { state: State::Closed, ..issue };
+
+        assert_eq!(
+            "reopen",
+            serde_json::json!(Slack.construct_actions(&closed, &reference, &[]))["elements"][0]["action_id"]
         );
     }
 
     #[test]
-    fn test_issue_text_lines_is_body_none() {
+    fn test_issue_draft() {
+        let author = SlackUser {
+            id: "U123ABC456".to_string(),
+            name: "steve".to_string(),
+            real_name: Some("Steve Smith".to_string()),
+            profile: SlackUserProfile { email: None },
+            is_admin: false,
+            is_owner: false,
+        };
+
+        assert_eq!(
+            (
+                "Login fails on Safari".to_string(),
+                "\nLogin fails on Safari\nsince yesterday\n\n---\nReported by Steve Smith in Slack: https://example.slack.com/archives/C123/p123".to_string(),
+            ),
+            Slack.issue_draft("\nLogin fails on Safari\nsince yesterday", Some(&author), "https://example.slack.com/archives/C123/p123")
+        );
+
+        let (title, body) = Slack.issue_draft(&"a".repeat(4000), None, "https://example.slack.com/archives/C123/p123");
+
+        assert_eq!(100, title.chars().count());
+        assert_eq!(3000, body.chars().count());
+        assert!(body.ends_with("Reported by unknown in Slack: https://example.slack.com/archives/C123/p123"));
+
+        let (_, body) = Slack.issue_draft("text", None, &"p".repeat(4000));
+
+        assert!(!body.starts_with("text"));
+    }
+
+    #[test]
+    fn test_construct_home_view() {
+        let item = SearchItem {
+            html_url: "https://github.com/reo0306/rust-slack-bot/pull/3".to_string(),
+            title: "Add App Home".to_string(),
+            number: 3,
+            repository_url: "https://api.github.com/repos/reo0306/rust-slack-bot".to_string(),
+        };
+
+        let view = Slack.construct_home_view(Some("reo0306"), &[], &[], &[(item, CiStatus::Success)]);
+
+        assert_eq!("home", view.r#type);
+        assert_eq!(
+            vec![
+                "Your GitHub work (reo0306)",
+                "*Assigned issues*",
+                "_Nothing here_ :tada:",
+                "*Pull requests awaiting your review*",
+                "_Nothing here_ :tada:",
+                "*Your open pull requests*",
+                ":white_check_mark: • <https://github.com/reo0306/rust-slack-bot/pull/3|reo0306/rust-slack-bot#3> Add App Home",
+            ],
+            view.blocks.iter().filter_map(Block::text).collect::<Vec<_>>()
+        );
+
+        let view = Slack.construct_home_view(None, &[], &[], &[]);
+
+        assert_eq!(
+            serde_json::json!({
+                "type": "home",
+                "blocks": [
+                    {"type": "header", "text": {"type": "plain_text", "text": "Your GitHub work"}},
+                    {"type": "section", "text": {"type": "mrkdwn", "text": "Your Slack account is not linked to a GitHub account yet."}}
+                ]
+            }),
+            serde_json::json!(view)
+        );
+    }
+
+    #[test]
+    fn test_reference_summary() {
         let issue = Issue {
             html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
             title: "test".to_string(),
-            body: None,
-            state: "open".to_string(),
+            body: Some("body".to_string()),
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
             created_at: "2024-07-07T20:09:31Z".to_string(),
             number: 1,
             user: User {
                 html_url: "https://github.com/signalnerve".to_string(),
                 login: "test".to_string(),
                 avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-            }
+            },
+            pull_request: None,
+        };
+
+        let reference = IssueReference {
+            owner: "cloudflare".to_string(),
+            repo: "wrangler-legacy".to_string(),
+            number: 1,
         };
 
         assert_eq!(
-            "*test - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\n*open* - Created by <https://github.com/signalnerve|test> on 2024-07-07 20:09:31".to_string(),
-            Slack.text_lines(
-                &issue,
-                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
-                ""
-            )
+            "• <https://github.com/cloudflare/wrangler-legacy/issues/1|cloudflare/wrangler-legacy#1> test (open)\n• <https://github.com/cloudflare/wrangler-legacy/issues/1|cloudflare/wrangler-legacy#1> test (open)".to_string(),
+            Slack.reference_summary(&[(reference.clone(), issue.clone()), (reference, issue)])
         );
     }
 
     #[test]
-    fn test_pull_request_text_lines() {
-        let pull_request = PullRequest {
-            html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
-            title: "test pull_request".to_string(),
-            body: Some("body pull_request".to_string()),
-            state: "open".to_string(),
-            created_at: "2024-07-07T20:09:31Z".to_string(),
-            number: 2,
-            user: User {
-                html_url: "https://github.com/reo0306".to_string(),
-                login: "test2".to_string(),
-                avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-            }
+    fn test_routing_table_text() {
+        let mut table = RoutingTable::default();
+
+        assert_eq!("No routes yet, all events go to the default channel", Slack.routing_table_text(&table));
+
+        table.add(&RouteScope::Repository { repository: "org/api".to_string(), event: None }, "C1");
+        table.add(&RouteScope::Repository { repository: "org/api".to_string(), event: Some("pull_request".to_string()) }, "C2");
+        table.add(&RouteScope::Default, "C3");
+
+        assert_eq!(
+            "• org/api → <#C1>\n• org/api `pull_request` → <#C2>\nEverything else → <#C3>",
+            Slack.routing_table_text(&table)
+        );
+
+        assert_eq!("This channel will no longer receive events of org/api", Slack.route_text(&RouteScope::Repository { repository: "org/api".to_string(), event: None }, false));
+        assert_eq!("This channel does not receive events no route matches through a route", Slack.not_routed_text(&RouteScope::Default));
+    }
+
+    #[test]
+    fn test_share_text() {
+        assert_eq!("Org/API is shared with this channel", Slack.share_text("Org/API", true));
+        assert_eq!("Org/API is no longer shared with this channel", Slack.share_text("Org/API", false));
+    }
+
+    #[test]
+    fn test_create_rollup_message() {
+        let rollup = Rollup {
+            sink: MessageSink::IncomingWebhook("https://hooks.slack.com/x".to_string()),
+            entries: vec![RollupEntry {
+                repository: "org/api".to_string(),
+                number: Some(7),
+                title: "Bump <serde>".to_string(),
+                html_url: "https://github.com/org/api/pull/7".to_string(),
+                author: "dependabot[bot]".to_string(),
+                actor: None,
+                kind: ItemKind::PullRequest,
+                action: "opened".to_string(),
+            }],
+            keys: vec![],
         };
+        let message = Slack.create_rollup_message(&rollup, Queue::Rollup);
 
+        assert_eq!("Bot activity: 1 pull request opened", message.text);
+        assert_eq!("While this channel was quiet: 1 pull request opened", Slack.create_rollup_message(&rollup, Queue::CatchUp).text);
         assert_eq!(
-            "*test pull_request - <https://github.com/reo0306/rust-todo-di-app/pull/1|>*\nbody pull_request\n*open* - Created by <https://github.com/reo0306|test2> on 2024-07-07 20:09:31".to_string(),
-            Slack.text_lines(
-                &pull_request,
-                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
-                ""
-            )
+            Block::section(Text::mrkdwn("• <https://github.com/org/api/pull/7|org/api#7> Bump &lt;serde&gt; (opened by dependabot[bot])")),
+            message.blocks[1]
         );
     }
 
     #[test]
-    fn test_pull_request_text_lines_is_body_none() {
-        let pull_request = PullRequest {
-            html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
-            title: "test pull_request".to_string(),
-            body: None,
-            state: "open".to_string(),
+    fn test_create_digest_message() {
+        let entry = |kind, number: Option<u32>, action: &str, actor: &str| RollupEntry {
+            repository: "org/api".to_string(),
+            number,
+            title: "Fix <login>".to_string(),
+            html_url: format!("https://github.com/org/api/issues/{}", number.unwrap_or_default()),
+            author: "monalisa".to_string(),
+            actor: Some(actor.to_string()),
+            kind,
+            action: action.to_string(),
+        };
+        let rollup = Rollup {
+            sink: MessageSink::Channel { team_id: "T1".to_string(), channel: "C1".to_string() },
+            entries: vec![
+                entry(ItemKind::Issue, Some(1), "opened", "monalisa"),
+                entry(ItemKind::PullRequest, Some(2), "merged", "hubot"),
+                entry(ItemKind::Release, None, "published", "hubot"),
+            ],
+            keys: vec![],
+        };
+        let message = Slack.create_digest_message(&rollup, Frequency::Weekly);
+
+        assert_eq!("Weekly digest: 1 issues opened, 0 closed, 1 pull requests merged, 1 releases", message.text);
+        assert_eq!(
+            vec![
+                Block::header("Weekly digest"),
+                Block::section(Text::mrkdwn("*Issues opened (1)*\n• <https://github.com/org/api/issues/1|org/api#1> Fix &lt;login&gt;")),
+                Block::section(Text::mrkdwn("*Pull requests merged (1)*\n• <https://github.com/org/api/issues/2|org/api#2> Fix &lt;login&gt;")),
+                Block::section(Text::mrkdwn("*Releases (1)*\n• <https://github.com/org/api/issues/0|org/api> Fix &lt;login&gt;")),
+                Block::divider(),
+                Block::context(vec![ContextElement::Text(Text::mrkdwn("Top contributors: <https://github.com/hubot|hubot> (2), <https://github.com/monalisa|monalisa> (1)"))]),
+            ],
+            message.blocks
+        );
+    }
+
+    #[test]
+    fn test_quiet_hours_text() {
+        assert_eq!(
+            "Between 22:00 and 07:00 (Asia/Tokyo) this channel only receives urgent events; the rest follows as a catch-up summary",
+            Slack.quiet_hours_text(QuietHours::parse("22:00-07:00", "Asia/Tokyo").ok().as_ref())
+        );
+        assert_eq!("Quiet hours are off, this channel receives events as they happen", Slack.quiet_hours_text(None));
+    }
+
+    #[test]
+    fn test_subscriptions_text() {
+        let mut subscriptions = ChannelSubscriptions::default();
+
+        assert_eq!("This channel has no subscriptions yet", Slack.subscriptions_text(&subscriptions));
+
+        subscriptions.subscribe("org/api", &[Category::Issues, Category::Pulls]);
+        subscriptions.subscribe("org/web", &[Category::Releases]);
+
+        assert_eq!("• org/api: issues, pulls\n• org/web: releases", Slack.subscriptions_text(&subscriptions));
+        assert_eq!("This channel receives issues, pulls of org/api", Slack.subscription_text("org/api", subscriptions.subscription("org/api")));
+        assert_eq!("This channel no longer receives events of org/cli", Slack.subscription_text("org/cli", None));
+
+        subscriptions.set_filter("org/web", &[Rule::parse("+base:release/*").unwrap(), Rule::parse("-author:renovate[bot]").unwrap()]);
+
+        assert_eq!(
+            "This channel receives releases of org/web matching `+base:release/*` `-author:renovate[bot]`",
+            Slack.subscription_text("org/web", subscriptions.subscription("org/web"))
+        );
+    }
+
+    #[test]
+    fn test_construct_commit_message() {
+        let commit: Commit = serde_json::from_str(r#"{"sha":"7853d35a8f1e2c3b4d5e6f708192a3b4c5d6e7f8","html_url":"https://github.com/reo0306/rust-slack-bot/commit/7853d35a8f1e2c3b4d5e6f708192a3b4c5d6e7f8","commit":{"message":"baseline\n\nlong description","author":{"name":"reo","date":"2024-07-07T20:09:31Z"}},"author":null}"#).unwrap();
+
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn("*baseline - <https://github.com/reo0306/rust-slack-bot/commit/7853d35a8f1e2c3b4d5e6f708192a3b4c5d6e7f8|reo0306/rust-slack-bot@7853d35>*\nCommitted by reo on 2024-07-07 20:09:31"))
+                    .with_accessory(Element::image("https://github.com/ghost.png", "reo"))
+            ],
+            Slack.construct_commit_message(&commit, "reo0306", "rust-slack-bot")
+        );
+    }
+
+    #[test]
+    fn test_construct_commit_message_is_unknown_date() {
+        let commit: Commit = serde_json::from_str(r#"{"sha":"7853d35","html_url":"https://github.com/reo0306/rust-slack-bot/commit/7853d35","commit":{"message":"baseline","author":{"name":"reo","date":"2024-07-07T20:09:31+09:00"}},"author":null}"#).unwrap();
+
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn("*baseline - <https://github.com/reo0306/rust-slack-bot/commit/7853d35|reo0306/rust-slack-bot@7853d35>*\nCommitted by reo on 2024-07-07T20:09:31+09:00"))
+                    .with_accessory(Element::image("https://github.com/ghost.png", "reo"))
+            ],
+            Slack.construct_commit_message(&commit, "reo0306", "rust-slack-bot")
+        );
+    }
+
+    const SIGNING_SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const SIGNED_BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+    const SIGNATURE: &str = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+
+    #[test]
+    fn test_verify_signature() {
+        assert!(Slack.verify_signature(SIGNING_SECRET, "1531420618", SIGNED_BODY, SIGNATURE, 1531420618));
+    }
+
+    #[test]
+    fn test_verify_signature_is_tampered() {
+        assert!(!Slack.verify_signature(SIGNING_SECRET, "1531420618", &format!("{}&text=rm", SIGNED_BODY), SIGNATURE, 1531420618));
+        assert!(!Slack.verify_signature("another_secret", "1531420618", SIGNED_BODY, SIGNATURE, 1531420618));
+        assert!(!Slack.verify_signature(SIGNING_SECRET, "1531420618", SIGNED_BODY, "a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503", 1531420618));
+    }
+
+    #[test]
+    fn test_verify_oauth_state() {
+        let state = Slack.oauth_state("client_secret", 1531420618);
+
+        assert!(Slack.verify_oauth_state("client_secret", &state, 1531420618 + 60));
+        assert!(!Slack.verify_oauth_state("another_secret", &state, 1531420618 + 60));
+        assert!(!Slack.verify_oauth_state("client_secret", &state, 1531420618 + 60 * 11));
+        assert!(!Slack.verify_oauth_state("client_secret", &state.replacen("1531420618", "1531420619", 1), 1531420619));
+        assert!(!Slack.verify_oauth_state("client_secret", "1531420618", 1531420618));
+    }
+
+    #[test]
+    fn test_verify_link_state() {
+        let state = Slack.link_state("client_secret", "T123ABC456", "U123ABC456", "octocat", 1531420618);
+
+        assert_eq!(
+            Some(("T123ABC456".to_string(), "U123ABC456".to_string(), "octocat".to_string())),
+            Slack.verify_link_state("client_secret", &state, 1531420618 + 60)
+        );
+        assert_eq!(None, Slack.verify_link_state("another_secret", &state, 1531420618 + 60));
+        assert_eq!(None, Slack.verify_link_state("client_secret", &state, 1531420618 + 60 * 11));
+        assert_eq!(None, Slack.verify_link_state("client_secret", &state.replacen("octocat", "hubot", 1), 1531420618));
+    }
+
+    #[test]
+    fn test_mention_users() {
+        let issue = Issue {
+            html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
+            title: "test".to_string(),
+            body: Some("cc [Octocat](https://github.com/Octocat) <https://github.com/someone|octocat>".to_string()),
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
             created_at: "2024-07-07T20:09:31Z".to_string(),
-            number: 2,
+            number: 1,
             user: User {
-                html_url: "https://github.com/reo0306".to_string(),
-                login: "test2".to_string(),
+                html_url: "https://github.com/Octocat".to_string(),
+                login: "Octocat".to_string(),
                 avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-            }
+            },
+            pull_request: None,
         };
 
+        let reference = IssueReference {
+            owner: "cloudflare".to_string(),
+            repo: "wrangler-legacy".to_string(),
+            number: 1,
+        };
+
+        let message = Slack.create_lookup_message(&issue, "", &reference, &[]);
+
+        assert_eq!(vec!["octocat".to_string()], message.github_logins());
+
+        let message = message.mention_users(&HashMap::from([("octocat".to_string(), "U123ABC456".to_string())]));
+
         assert_eq!(
-            "*test pull_request - <https://github.com/reo0306/rust-todo-di-app/pull/1|>*\n*open* - Created by <https://github.com/reo0306|test2> on 2024-07-07 20:09:31".to_string(),
-            Slack.text_lines(
-                &pull_request,
-                "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
-                ""
-            )
+            "*test - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\ncc <https://github.com/Octocat|Octocat> &lt;https://github.com/someone|octocat&gt;\n*open* - Created by <@U123ABC456> on 2024-07-07 20:09:31",
+            message.attachments[0].blocks[0].text().unwrap()
         );
     }
 
     #[test]
-    fn test_construct_message() {
+    fn test_hostile_payload_is_escaped() {
         let issue = Issue {
             html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
-            title: "test".to_string(),
-            body: Some("body".to_string()),
-            state: "open".to_string(),
+            title: "<!channel> <https://evil.example|Click> & @here".to_string(),
+            body: Some("<@U123ABC456> <#C123ABC456> @everyone".to_string()),
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
             created_at: "2024-07-07T20:09:31Z".to_string(),
             number: 1,
             user: User {
-                html_url: "https://github.com/signalnerve".to_string(),
-                login: "test".to_string(),
+                html_url: "https://github.com/Octocat".to_string(),
+                login: "Octocat".to_string(),
                 avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-            }
+            },
+            pull_request: None,
         };
 
-        let text_lines = Slack.text_lines(
-            &issue,
-            "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f",
-            ""
+        let reference = IssueReference {
+            owner: "cloudflare".to_string(),
+            repo: "wrangler-legacy".to_string(),
+            number: 1,
+        };
+
+        let message = Slack.create_lookup_message(&issue, "", &reference, &[]);
+
+        assert_eq!(
+            "*&lt;!channel&gt; &lt;https://evil.example|Click&gt; &amp; @\u{2060}here - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\n&lt;@U123ABC456&gt; &lt;#C123ABC456&gt; @\u{2060}everyone\n*open* - Created by <https://github.com/Octocat|Octocat\u{2063}> on 2024-07-07 20:09:31",
+            message.attachments[0].blocks[0].text().unwrap()
         );
 
         assert_eq!(
-            vec![
-                Blocks {
-                    r#type: "section".to_string(),
-                    text: Text {
-                        r#type: "mrkdwn".to_string(),
-                        text: text_lines.to_string(),
-                    },
-                    accessory: Accessory {
-                        r#type: "image".to_string(),
-                        image_url: issue.user.avatar_url.to_string(),
-                        alt_text: issue.user.login.to_string(),
-                    },
-                }
-            ],
-            Slack.construct_message(&issue, &text_lines)
+            "• <https://github.com/cloudflare/wrangler-legacy/issues/1|cloudflare/wrangler-legacy#1> &lt;!channel&gt; &lt;https://evil.example|Click&gt; &amp; @\u{2060}here (open)",
+            Slack.reference_summary(&[(reference, issue)])
         );
     }
+
+    #[test]
+    fn test_verify_signature_is_expired() {
+        assert!(!Slack.verify_signature(SIGNING_SECRET, "1531420618", SIGNED_BODY, SIGNATURE, 1531420618 + 60 * 6));
+    }
 }
@@ -0,0 +1,412 @@
+use std::error::Error;
+use sha2::{Digest, Sha256};
+use worker::{kv::KvStore, Date};
+
+use crate::domain::model::{
+    github::IssueReference,
+    quiet_hours::QuietHours,
+    rollup::{Queue, QueuedEntry, Rollup, RollupEntry},
+    routing::RoutingTable,
+    slack::{IndexedMessage, Installation, MessageSink, PostedMessage},
+    subscription::{ChannelSubscriptions, Subscriber, Subscription},
+};
+
+/// Cards older than this are no longer refreshed.
+const MESSAGE_INDEX_LIMIT: usize = 20;
+
+/// Workers KV refuses key metadata larger than this many bytes.
+const MAX_METADATA_SIZE: usize = 1024;
+
+pub struct Store;
+
+impl Store {
+    /// Slack channel IDs allowed to see content of a private repository, stored as a JSON array
+    /// under `repository_channels:{owner}/{repo}`.
+    pub async fn repository_channels(&self, kv: &KvStore, full_name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let channels = kv
+            .get(&format!("repository_channels:{}", full_name))
+            .json::<Vec<String>>()
+            .await?;
+
+        Ok(channels.unwrap_or_default())
+    }
+
+    pub async fn save_repository_channels(&self, kv: &KvStore, full_name: &str, channels: &[String]) -> Result<(), Box<dyn Error>> {
+        let key = format!("repository_channels:{}", full_name);
+
+        if channels.is_empty() {
+            kv.delete(&key).await?;
+        } else {
+            kv.put(&key, channels)?.execute().await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn routing_table(&self, kv: &KvStore, team_id: &str) -> Result<RoutingTable, Box<dyn Error>> {
+        let table = kv
+            .get(&format!("routing_table:{}", team_id))
+            .json::<RoutingTable>()
+            .await?;
+
+        Ok(table.unwrap_or_default())
+    }
+
+    pub async fn save_routing_table(&self, kv: &KvStore, team_id: &str, table: &RoutingTable) -> Result<(), Box<dyn Error>> {
+        kv.put(&format!("routing_table:{}", team_id), table)?
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn channel_subscriptions(&self, kv: &KvStore, team_id: &str, channel: &str) -> Result<ChannelSubscriptions, Box<dyn Error>> {
+        let subscriptions = kv
+            .get(&format!("subscriptions:{}:{}", team_id, channel))
+            .json::<ChannelSubscriptions>()
+            .await?;
+
+        Ok(subscriptions.unwrap_or_default())
+    }
+
+    pub async fn save_channel_subscriptions(&self, kv: &KvStore, team_id: &str, channel: &str, subscriptions: &ChannelSubscriptions) -> Result<(), Box<dyn Error>> {
+        let key = format!("subscriptions:{}:{}", team_id, channel);
+
+        if subscriptions.subscriptions.is_empty() {
+            kv.delete(&key).await?;
+        } else {
+            kv.put(&key, subscriptions)?.execute().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Channels subscribed to a repository with what they subscribed to, one key per channel under
+    /// `subscribers:{owner}/{repo}:{team}:{channel}` with the repository in lowercase. The subscription
+    /// rides along as key metadata so a listing is all it takes; only ones too large for it are read.
+    pub async fn subscribers(&self, kv: &KvStore, repository: &str) -> Result<Vec<(Subscriber, Subscription)>, Box<dyn Error>> {
+        let prefix = format!("subscribers:{}:", repository.to_lowercase());
+        let mut subscribers = vec![];
+        let mut cursor = None;
+
+        loop {
+            let mut list = kv.list().prefix(prefix.to_string());
+
+            if let Some(cursor) = cursor {
+                list = list.cursor(cursor);
+            }
+
+            let page = list.execute().await?;
+
+            for key in page.keys {
+                let (team_id, channel) = match key.name[prefix.len()..].split_once(':') {
+                    Some(ids) => ids,
+                    None => continue,
+                };
+                let subscription = match key.metadata.and_then(|metadata| serde_json::from_value::<Subscription>(metadata).ok()) {
+                    Some(subscription) => Some(subscription),
+                    None => kv.get(&key.name).json::<Subscription>().await?,
+                };
+
+                if let Some(subscription) = subscription {
+                    subscribers.push((Subscriber { team_id: team_id.to_string(), channel: channel.to_string() }, subscription));
+                }
+            }
+
+            match (page.list_complete, page.cursor) {
+                (false, Some(next)) => cursor = Some(next),
+                _ => return Ok(subscribers),
+            }
+        }
+    }
+
+    /// Each channel only ever writes its own key, so concurrent subscribes cannot drop one another.
+    pub async fn set_subscriber(&self, kv: &KvStore, repository: &str, subscriber: &Subscriber, subscription: Option<&Subscription>) -> Result<(), Box<dyn Error>> {
+        let key = format!("subscribers:{}:{}:{}", repository.to_lowercase(), subscriber.team_id, subscriber.channel);
+
+        match subscription {
+            Some(subscription) if serde_json::to_string(subscription)?.len() <= MAX_METADATA_SIZE => {
+                kv.put(&key, subscription)?.metadata(subscription)?.execute().await?
+            },
+            Some(subscription) => kv.put(&key, subscription)?.execute().await?,
+            None => kv.delete(&key).await?,
+        }
+
+        Ok(())
+    }
+
+    pub async fn quiet_hours(&self, kv: &KvStore, team_id: &str, channel: &str) -> Result<Option<QuietHours>, Box<dyn Error>> {
+        let quiet_hours = kv
+            .get(&format!("quiet_hours:{}:{}", team_id, channel))
+            .json::<QuietHours>()
+            .await?;
+
+        Ok(quiet_hours)
+    }
+
+    pub async fn set_quiet_hours(&self, kv: &KvStore, team_id: &str, channel: &str, quiet_hours: Option<&QuietHours>) -> Result<(), Box<dyn Error>> {
+        let key = format!("quiet_hours:{}:{}", team_id, channel);
+
+        match quiet_hours {
+            Some(quiet_hours) => kv.put(&key, quiet_hours)?.execute().await?,
+            None => kv.delete(&key).await?,
+        }
+
+        Ok(())
+    }
+
+    /// Events held back for `sink`, one key each under `{queue}:{team}:{channel}:` or `{queue}:webhook:`.
+    /// Keys start with the time they were queued at, so a listing returns them in order.
+    pub async fn enqueue(&self, kv: &KvStore, queue: Queue, sink: &MessageSink, entry: RollupEntry) -> Result<(), Box<dyn Error>> {
+        let queued = QueuedEntry { sink: sink.clone(), entry };
+        let digest = Sha256::digest(serde_json::to_vec(&queued)?);
+        let key = format!("{}{:013}:{}", Self::queue_prefix(queue, sink), Date::now().as_millis(), hex::encode(&digest[..8]));
+
+        kv.put(&key, queued)?.execute().await?;
+
+        Ok(())
+    }
+
+    /// Everything waiting in `queue`, by sink. Only the scheduled handler scans for it.
+    pub async fn queued(&self, kv: &KvStore, queue: Queue) -> Result<Vec<Rollup>, Box<dyn Error>> {
+        let mut queued = vec![];
+        let mut cursor = None;
+
+        loop {
+            let mut list = kv.list().prefix(format!("{}:", queue.prefix()));
+
+            if let Some(cursor) = cursor {
+                list = list.cursor(cursor);
+            }
+
+            let page = list.execute().await?;
+
+            for key in page.keys {
+                if let Some(entry) = kv.get(&key.name).json::<QueuedEntry>().await? {
+                    queued.push((key.name, entry));
+                }
+            }
+
+            match (page.list_complete, page.cursor) {
+                (false, Some(next)) => cursor = Some(next),
+                _ => return Ok(Rollup::group(queued)),
+            }
+        }
+    }
+
+    /// Deletes only the entries that were delivered; ones queued in the meantime stay for the next run.
+    pub async fn dequeue(&self, kv: &KvStore, keys: &[String]) -> Result<(), Box<dyn Error>> {
+        for key in keys {
+            kv.delete(key).await?;
+        }
+
+        Ok(())
+    }
+
+    /// References typed by users keep their casing, while webhooks name repositories as GitHub does.
+    fn reference_key(reference: &IssueReference) -> String {
+        reference.to_string().to_lowercase()
+    }
+
+    fn queue_prefix(queue: Queue, sink: &MessageSink) -> String {
+        match sink {
+            MessageSink::Channel { team_id, channel } => format!("{}:{}:{}:", queue.prefix(), team_id, channel),
+            MessageSink::IncomingWebhook(_) => format!("{}:webhook:", queue.prefix()),
+        }
+    }
+
+    pub async fn auto_expand_enabled(&self, kv: &KvStore, channel: &str) -> Result<bool, Box<dyn Error>> {
+        let enabled = kv
+            .get(&format!("auto_expand:{}", channel))
+            .json::<bool>()
+            .await?;
+
+        Ok(enabled.unwrap_or(false))
+    }
+
+    pub async fn set_auto_expand(&self, kv: &KvStore, channel: &str, enabled: bool) -> Result<(), Box<dyn Error>> {
+        let key = format!("auto_expand:{}", channel);
+
+        if enabled {
+            kv.put(&key, true)?.execute().await?;
+        } else {
+            kv.delete(&key).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn github_login(&self, kv: &KvStore, team_id: &str, user_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let login = kv
+            .get(&format!("github_login:{}:{}", team_id, user_id))
+            .text()
+            .await?;
+
+        Ok(login)
+    }
+
+    pub async fn slack_user(&self, kv: &KvStore, team_id: &str, login: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let user_id = kv
+            .get(&format!("slack_user:{}:{}", team_id, login.to_lowercase()))
+            .text()
+            .await?;
+
+        Ok(user_id)
+    }
+
+    /// Links a Slack user to a GitHub login in both directions, dropping the login the user was linked to before.
+    pub async fn link_user(&self, kv: &KvStore, team_id: &str, user_id: &str, login: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(previous) = self.github_login(kv, team_id, user_id).await? {
+            kv.delete(&format!("slack_user:{}:{}", team_id, previous.to_lowercase())).await?;
+        }
+
+        kv.put(&format!("github_login:{}:{}", team_id, user_id), login)?
+            .execute()
+            .await?;
+        kv.put(&format!("slack_user:{}:{}", team_id, login.to_lowercase()), user_id)?
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn direct_messages_enabled(&self, kv: &KvStore, team_id: &str, user_id: &str) -> Result<bool, Box<dyn Error>> {
+        let opted_out = kv
+            .get(&format!("dm_opt_out:{}:{}", team_id, user_id))
+            .json::<bool>()
+            .await?;
+
+        Ok(!opted_out.unwrap_or(false))
+    }
+
+    pub async fn set_direct_messages(&self, kv: &KvStore, team_id: &str, user_id: &str, enabled: bool) -> Result<(), Box<dyn Error>> {
+        let key = format!("dm_opt_out:{}:{}", team_id, user_id);
+
+        if enabled {
+            kv.delete(&key).await?;
+        } else {
+            kv.put(&key, true)?.execute().await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn installation(&self, kv: &KvStore, team_id: &str) -> Result<Option<Installation>, Box<dyn Error>> {
+        let installation = kv
+            .get(&format!("installation:{}", team_id))
+            .json::<Installation>()
+            .await?;
+
+        Ok(installation)
+    }
+
+    /// Team IDs of every workspace the app was installed to through OAuth.
+    pub async fn installed_teams(&self, kv: &KvStore) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut teams = vec![];
+        let mut cursor = None;
+
+        loop {
+            let mut list = kv.list().prefix("installation:".to_string());
+
+            if let Some(cursor) = cursor {
+                list = list.cursor(cursor);
+            }
+
+            let page = list.execute().await?;
+
+            teams.extend(page.keys.into_iter().filter_map(|key| key.name.strip_prefix("installation:").map(str::to_string)));
+
+            match (page.list_complete, page.cursor) {
+                (false, Some(next)) => cursor = Some(next),
+                _ => return Ok(teams),
+            }
+        }
+    }
+
+    pub async fn save_installation(&self, kv: &KvStore, installation: &Installation) -> Result<(), Box<dyn Error>> {
+        kv.put(&format!("installation:{}", installation.team_id), installation)?
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_installation(&self, kv: &KvStore, team_id: &str) -> Result<(), Box<dyn Error>> {
+        kv.delete(&format!("installation:{}", team_id)).await?;
+
+        Ok(())
+    }
+
+    /// The first message posted for an issue or pull request in a channel, under which follow-ups are threaded.
+    pub async fn thread(&self, kv: &KvStore, channel: &str, reference: &IssueReference) -> Result<Option<PostedMessage>, Box<dyn Error>> {
+        let thread = kv
+            .get(&format!("thread:{}:{}", channel, Self::reference_key(reference)))
+            .json::<PostedMessage>()
+            .await?;
+
+        Ok(thread)
+    }
+
+    pub async fn save_thread(&self, kv: &KvStore, reference: &IssueReference, posted: &PostedMessage) -> Result<(), Box<dyn Error>> {
+        kv.put(&format!("thread:{}:{}", posted.channel, Self::reference_key(reference)), posted)?
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    /// The ID Slack answered with when the bot first posted to a channel configured by name, such as `#dev`.
+    pub async fn channel_id(&self, kv: &KvStore, team_id: &str, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(kv.get(&format!("channel_id:{}:{}", team_id, name)).text().await?)
+    }
+
+    pub async fn save_channel_id(&self, kv: &KvStore, team_id: &str, name: &str, channel_id: &str) -> Result<(), Box<dyn Error>> {
+        kv.put(&format!("channel_id:{}:{}", team_id, name), channel_id)?
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Cards posted for an issue or pull request, oldest first, under `messages:{owner}/{repo}#{number}` in lowercase.
+    pub async fn indexed_messages(&self, kv: &KvStore, reference: &IssueReference) -> Result<Vec<IndexedMessage>, Box<dyn Error>> {
+        let messages = kv
+            .get(&format!("messages:{}", Self::reference_key(reference)))
+            .json::<Vec<IndexedMessage>>()
+            .await?;
+
+        Ok(messages.unwrap_or_default())
+    }
+
+    pub async fn index_message(&self, kv: &KvStore, reference: &IssueReference, message: IndexedMessage) -> Result<(), Box<dyn Error>> {
+        let mut messages = self.indexed_messages(kv, reference).await?;
+
+        if messages.contains(&message) {
+            return Ok(());
+        }
+
+        messages.push(message);
+
+        if messages.len() > MESSAGE_INDEX_LIMIT {
+            messages.drain(..messages.len() - MESSAGE_INDEX_LIMIT);
+        }
+
+        kv.put(&format!("messages:{}", Self::reference_key(reference)), messages)?
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_message_index(&self, kv: &KvStore, reference: &IssueReference) -> Result<(), Box<dyn Error>> {
+        kv.delete(&format!("messages:{}", Self::reference_key(reference))).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_thread(&self, kv: &KvStore, channel: &str, reference: &IssueReference) -> Result<(), Box<dyn Error>> {
+        kv.delete(&format!("thread:{}:{}", channel, Self::reference_key(reference))).await?;
+
+        Ok(())
+    }
+}
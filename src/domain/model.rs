@@ -1,3 +1,12 @@
+pub mod block_kit;
+pub mod command;
+pub mod digest;
+pub mod filter;
 pub mod routes;
+pub mod rollup;
+pub mod routing;
 pub mod github;
+pub mod mrkdwn;
+pub mod quiet_hours;
 pub mod slack;
+pub mod subscription;
@@ -0,0 +1,775 @@
+use serde::{Serialize, Deserialize};
+
+use crate::domain::model::mrkdwn;
+
+/// Slack rejects messages with more blocks than this.
+pub const MAX_MESSAGE_BLOCKS: usize = 50;
+/// Modals and App Home tabs may hold twice as many blocks as a message.
+pub const MAX_VIEW_BLOCKS: usize = 100;
+/// Slack truncates messages longer than this.
+pub const MAX_MESSAGE_CHARACTERS: usize = 40_000;
+/// Longest text a section accepts.
+pub const MAX_SECTION_TEXT: usize = 3000;
+/// Longest text of a select option.
+pub const MAX_OPTION_TEXT: usize = 75;
+/// Longest value of a select option.
+pub const MAX_OPTION_VALUE: usize = 150;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    PlainText {
+        text: String,
+    },
+    Mrkdwn {
+        text: String,
+    },
+}
+
+impl Text {
+    pub fn plain(text: &str) -> Text {
+        Text::PlainText {
+            text: text.to_string(),
+        }
+    }
+
+    pub fn mrkdwn(text: &str) -> Text {
+        Text::Mrkdwn {
+            text: text.to_string(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            Text::PlainText { text } | Text::Mrkdwn { text } => text,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct OptionObject {
+    pub text: Text,
+    pub value: String,
+}
+
+impl OptionObject {
+    /// Long texts, such as repository names, are shortened to fit; the value is kept as it is.
+    pub fn new(text: &str, value: &str) -> OptionObject {
+        let text = match text.chars().count() > MAX_OPTION_TEXT {
+            true => text.chars().take(MAX_OPTION_TEXT - 1).chain(['…']).collect(),
+            false => text.to_string(),
+        };
+
+        OptionObject {
+            text: Text::plain(&text),
+            value: value.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonStyle {
+    Primary,
+    Danger,
+}
+
+/// Interactive and image elements, used in actions and input blocks and as section accessories.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Button {
+        action_id: String,
+        text: Text,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        style: Option<ButtonStyle>,
+    },
+    StaticSelect {
+        action_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        placeholder: Option<Text>,
+        options: Vec<OptionObject>,
+    },
+    MultiStaticSelect {
+        action_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        placeholder: Option<Text>,
+        options: Vec<OptionObject>,
+    },
+    PlainTextInput {
+        action_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        placeholder: Option<Text>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        initial_value: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multiline: Option<bool>,
+    },
+    Image {
+        image_url: String,
+        alt_text: String,
+    },
+}
+
+impl Element {
+    pub fn button(action_id: &str, text: &str, value: &str) -> Element {
+        Element::Button {
+            action_id: action_id.to_string(),
+            text: Text::plain(text),
+            value: Some(value.to_string()),
+            url: None,
+            style: None,
+        }
+    }
+
+    pub fn link_button(action_id: &str, text: &str, url: &str) -> Element {
+        Element::Button {
+            action_id: action_id.to_string(),
+            text: Text::plain(text),
+            value: None,
+            url: Some(url.to_string()),
+            style: None,
+        }
+    }
+
+    pub fn static_select(action_id: &str, placeholder: &str, options: Vec<OptionObject>) -> Element {
+        Element::StaticSelect {
+            action_id: action_id.to_string(),
+            placeholder: Some(Text::plain(placeholder)),
+            options,
+        }
+    }
+
+    pub fn multi_static_select(action_id: &str, placeholder: &str, options: Vec<OptionObject>) -> Element {
+        Element::MultiStaticSelect {
+            action_id: action_id.to_string(),
+            placeholder: Some(Text::plain(placeholder)),
+            options,
+        }
+    }
+
+    pub fn plain_text_input(action_id: &str) -> Element {
+        Element::PlainTextInput {
+            action_id: action_id.to_string(),
+            placeholder: None,
+            initial_value: None,
+            multiline: None,
+        }
+    }
+
+    pub fn image(image_url: &str, alt_text: &str) -> Element {
+        Element::Image {
+            image_url: image_url.to_string(),
+            alt_text: alt_text.to_string(),
+        }
+    }
+
+    /// Only buttons have a style; other elements are returned unchanged.
+    pub fn with_style(self, style: ButtonStyle) -> Element {
+        match self {
+            Element::Button { action_id, text, value, url, .. } => Element::Button { action_id, text, value, url, style: Some(style) },
+            element => element,
+        }
+    }
+
+    /// Only plain text inputs have an initial value; other elements are returned unchanged.
+    pub fn with_initial_value(self, value: &str) -> Element {
+        match self {
+            Element::PlainTextInput { action_id, placeholder, multiline, .. } => Element::PlainTextInput {
+                action_id,
+                placeholder,
+                initial_value: Some(value.to_string()),
+                multiline,
+            },
+            element => element,
+        }
+    }
+
+    pub fn multiline(self) -> Element {
+        match self {
+            Element::PlainTextInput { action_id, placeholder, initial_value, .. } => Element::PlainTextInput {
+                action_id,
+                placeholder,
+                initial_value,
+                multiline: Some(true),
+            },
+            element => element,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Element::Button { action_id, text, value, url, .. } => {
+                check_length("button action_id", action_id, 255)?;
+                check_length("button text", text.text(), 75)?;
+                check_length("button value", value.as_deref().unwrap_or(""), 2000)?;
+                check_length("button url", url.as_deref().unwrap_or(""), 3000)
+            },
+            Element::StaticSelect { action_id, placeholder, options } | Element::MultiStaticSelect { action_id, placeholder, options } => {
+                check_length("select action_id", action_id, 255)?;
+                check_length("select placeholder", placeholder.as_ref().map(Text::text).unwrap_or(""), 150)?;
+                check_count("select options", options.len(), 100)?;
+
+                for option in options {
+                    check_length("option text", option.text.text(), MAX_OPTION_TEXT)?;
+                    check_length("option value", &option.value, MAX_OPTION_VALUE)?;
+                }
+
+                Ok(())
+            },
+            Element::PlainTextInput { action_id, placeholder, .. } => {
+                check_length("input action_id", action_id, 255)?;
+                check_length("input placeholder", placeholder.as_ref().map(Text::text).unwrap_or(""), 150)
+            },
+            Element::Image { image_url, alt_text } => {
+                check_length("image url", image_url, 3000)?;
+                check_length("image alt_text", alt_text, 2000)
+            },
+        }
+    }
+}
+
+/// Context blocks mix text and small images.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum ContextElement {
+    Text(Text),
+    Image(Element),
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct RichTextStyle {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bold: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strike: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub code: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RichTextInline {
+    Text {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        style: Option<RichTextStyle>,
+    },
+    Link {
+        url: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    User {
+        user_id: String,
+    },
+    Emoji {
+        name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RichTextListStyle {
+    Bullet,
+    Ordered,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum RichTextElement {
+    #[serde(rename = "rich_text_section")]
+    Section {
+        elements: Vec<RichTextInline>,
+    },
+    #[serde(rename = "rich_text_preformatted")]
+    Preformatted {
+        elements: Vec<RichTextInline>,
+    },
+    #[serde(rename = "rich_text_quote")]
+    Quote {
+        elements: Vec<RichTextInline>,
+    },
+    #[serde(rename = "rich_text_list")]
+    List {
+        style: RichTextListStyle,
+        elements: Vec<RichTextElement>,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Section {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<Text>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fields: Option<Vec<Text>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        accessory: Option<Element>,
+    },
+    Header {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+        text: Text,
+    },
+    Context {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+        elements: Vec<ContextElement>,
+    },
+    Divider {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+    },
+    Actions {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+        elements: Vec<Element>,
+    },
+    Image {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+        image_url: String,
+        alt_text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<Text>,
+    },
+    Input {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+        label: Text,
+        element: Element,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        dispatch_action: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        optional: Option<bool>,
+    },
+    RichText {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_id: Option<String>,
+        elements: Vec<RichTextElement>,
+    },
+}
+
+impl Block {
+    pub fn section(text: Text) -> Block {
+        Block::Section {
+            block_id: None,
+            text: Some(text),
+            fields: None,
+            accessory: None,
+        }
+    }
+
+    pub fn header(text: &str) -> Block {
+        Block::Header {
+            block_id: None,
+            text: Text::plain(text),
+        }
+    }
+
+    pub fn context(elements: Vec<ContextElement>) -> Block {
+        Block::Context {
+            block_id: None,
+            elements,
+        }
+    }
+
+    pub fn divider() -> Block {
+        Block::Divider {
+            block_id: None,
+        }
+    }
+
+    pub fn actions(elements: Vec<Element>) -> Block {
+        Block::Actions {
+            block_id: None,
+            elements,
+        }
+    }
+
+    pub fn input(label: &str, element: Element) -> Block {
+        Block::Input {
+            block_id: None,
+            label: Text::plain(label),
+            element,
+            dispatch_action: None,
+            optional: None,
+        }
+    }
+
+    pub fn with_block_id(mut self, id: &str) -> Block {
+        match &mut self {
+            Block::Section { block_id, .. }
+            | Block::Header { block_id, .. }
+            | Block::Context { block_id, .. }
+            | Block::Divider { block_id }
+            | Block::Actions { block_id, .. }
+            | Block::Image { block_id, .. }
+            | Block::Input { block_id, .. }
+            | Block::RichText { block_id, .. } => *block_id = Some(id.to_string()),
+        }
+
+        self
+    }
+
+    /// Only sections have an accessory; other blocks are returned unchanged.
+    pub fn with_accessory(mut self, element: Element) -> Block {
+        if let Block::Section { accessory, .. } = &mut self {
+            *accessory = Some(element);
+        }
+
+        self
+    }
+
+    pub fn optional(mut self) -> Block {
+        if let Block::Input { optional, .. } = &mut self {
+            *optional = Some(true);
+        }
+
+        self
+    }
+
+    /// Makes an input block send `block_actions` as soon as its element changes.
+    pub fn dispatch_action(mut self) -> Block {
+        if let Block::Input { dispatch_action, .. } = &mut self {
+            *dispatch_action = Some(true);
+        }
+
+        self
+    }
+
+    /// The main text of a section or header.
+    #[cfg(test)]
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Block::Section { text, .. } => text.as_ref().map(Text::text),
+            Block::Header { text, .. } => Some(text.text()),
+            _ => None,
+        }
+    }
+
+    /// The mrkdwn texts of the block, where links and mentions can appear.
+    pub fn mrkdwn_texts(&self) -> Vec<&String> {
+        let mut texts: Vec<&Text> = vec![];
+
+        match self {
+            Block::Section { text, fields, .. } => {
+                texts.extend(text.iter());
+                texts.extend(fields.iter().flatten());
+            },
+            Block::Context { elements, .. } => {
+                texts.extend(elements.iter().filter_map(|element| match element {
+                    ContextElement::Text(text) => Some(text),
+                    ContextElement::Image(_) => None,
+                }));
+            },
+            _ => {},
+        }
+
+        texts
+            .into_iter()
+            .filter_map(|text| match text {
+                Text::Mrkdwn { text } => Some(text),
+                Text::PlainText { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn mrkdwn_texts_mut(&mut self) -> Vec<&mut String> {
+        let mut texts: Vec<&mut Text> = vec![];
+
+        match self {
+            Block::Section { text, fields, .. } => {
+                texts.extend(text.iter_mut());
+                texts.extend(fields.iter_mut().flatten());
+            },
+            Block::Context { elements, .. } => {
+                texts.extend(elements.iter_mut().filter_map(|element| match element {
+                    ContextElement::Text(text) => Some(text),
+                    ContextElement::Image(_) => None,
+                }));
+            },
+            _ => {},
+        }
+
+        texts
+            .into_iter()
+            .filter_map(|text| match text {
+                Text::Mrkdwn { text } => Some(text),
+                Text::PlainText { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Spreads a section whose text is too long over several sections; the accessory and block id
+    /// stay with the first one. Other blocks are returned as they are.
+    pub fn split(self) -> Vec<Block> {
+        match self {
+            Block::Section { block_id, text: Some(text), fields, accessory } if text.text().chars().count() > MAX_SECTION_TEXT => {
+                let mut blocks = vec![];
+
+                for (i, chunk) in mrkdwn::split(text.text(), MAX_SECTION_TEXT).iter().enumerate() {
+                    let text = match text {
+                        Text::PlainText { .. } => Text::plain(chunk),
+                        Text::Mrkdwn { .. } => Text::mrkdwn(chunk),
+                    };
+
+                    blocks.push(match i {
+                        0 => Block::Section { block_id: block_id.clone(), text: Some(text), fields: None, accessory: accessory.clone() },
+                        _ => Block::section(text),
+                    });
+                }
+
+                if fields.is_some() {
+                    blocks.push(Block::Section { block_id: None, text: None, fields, accessory: None });
+                }
+
+                blocks
+            },
+            block => vec![block],
+        }
+    }
+
+    /// Checks the block against Slack's documented length and count limits.
+    pub fn validate(&self) -> Result<(), String> {
+        let block_id = match self {
+            Block::Section { block_id, .. }
+            | Block::Header { block_id, .. }
+            | Block::Context { block_id, .. }
+            | Block::Divider { block_id }
+            | Block::Actions { block_id, .. }
+            | Block::Image { block_id, .. }
+            | Block::Input { block_id, .. }
+            | Block::RichText { block_id, .. } => block_id,
+        };
+
+        check_length("block_id", block_id.as_deref().unwrap_or(""), 255)?;
+
+        match self {
+            Block::Section { text, fields, accessory, .. } => {
+                if text.is_none() && fields.is_none() {
+                    return Err("section needs text or fields".to_string());
+                }
+
+                check_length("section text", text.as_ref().map(Text::text).unwrap_or(""), MAX_SECTION_TEXT)?;
+
+                if let Some(fields) = fields {
+                    check_count("section fields", fields.len(), 10)?;
+
+                    for field in fields {
+                        check_length("section field", field.text(), 2000)?;
+                    }
+                }
+
+                accessory.as_ref().map(Element::validate).unwrap_or(Ok(()))
+            },
+            Block::Header { text, .. } => match text {
+                Text::PlainText { text } => check_length("header text", text, 150),
+                Text::Mrkdwn { .. } => Err("header text must be plain_text".to_string()),
+            },
+            Block::Context { elements, .. } => {
+                check_count("context elements", elements.len(), 10)?;
+
+                for element in elements {
+                    match element {
+                        ContextElement::Text(text) => check_length("context text", text.text(), 3000)?,
+                        ContextElement::Image(image @ Element::Image { .. }) => image.validate()?,
+                        ContextElement::Image(_) => return Err("context elements must be text or images".to_string()),
+                    }
+                }
+
+                Ok(())
+            },
+            Block::Divider { .. } => Ok(()),
+            Block::Actions { elements, .. } => {
+                check_count("actions elements", elements.len(), 25)?;
+
+                elements.iter().try_for_each(Element::validate)
+            },
+            Block::Image { image_url, alt_text, title, .. } => {
+                check_length("image url", image_url, 3000)?;
+                check_length("image alt_text", alt_text, 2000)?;
+                check_length("image title", title.as_ref().map(Text::text).unwrap_or(""), 2000)
+            },
+            Block::Input { label, element, .. } => {
+                check_length("input label", label.text(), 2000)?;
+
+                element.validate()
+            },
+            Block::RichText { .. } => Ok(()),
+        }
+    }
+}
+
+/// Blocks shown next to a colour bar, which is the only way to colour part of a message.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Attachment {
+    pub color: String,
+    pub blocks: Vec<Block>,
+}
+
+impl Attachment {
+    pub fn new(color: &str, blocks: Vec<Block>) -> Attachment {
+        Attachment {
+            color: color.to_string(),
+            blocks,
+        }
+    }
+}
+
+/// Checks a whole message or view; `max_blocks` is `MAX_MESSAGE_BLOCKS` or `MAX_VIEW_BLOCKS`.
+pub fn validate_blocks(blocks: &[Block], max_blocks: usize) -> Result<(), String> {
+    check_count("blocks", blocks.len(), max_blocks)?;
+
+    blocks.iter().try_for_each(Block::validate)
+}
+
+fn check_length(what: &str, value: &str, max: usize) -> Result<(), String> {
+    let length = value.chars().count();
+
+    if length > max {
+        return Err(format!("{} is {} characters long, Slack allows at most {}", what, length, max));
+    }
+
+    Ok(())
+}
+
+fn check_count(what: &str, count: usize, max: usize) -> Result<(), String> {
+    if count > max {
+        return Err(format!("{} has {} items, Slack allows at most {}", what, count, max));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod block_kit_test {
+    use super::*;
+
+    #[test]
+    fn test_split_section() {
+        let block = Block::section(Text::mrkdwn(&"line\n".repeat(1000)))
+            .with_accessory(Element::image("https://example.com/a.png", "a"))
+            .with_block_id("card");
+
+        let blocks = block.split();
+
+        assert_eq!(2, blocks.len());
+        assert!(blocks.iter().all(|block| block.validate().is_ok()));
+        assert_eq!(Some("card"), serde_json::to_value(&blocks[0]).unwrap()["block_id"].as_str());
+        assert!(serde_json::to_value(&blocks[0]).unwrap().get("accessory").is_some());
+        assert!(serde_json::to_value(&blocks[1]).unwrap().get("accessory").is_none());
+
+        assert_eq!(vec![Block::divider()], Block::divider().split());
+    }
+
+    #[test]
+    fn test_serialize_section() {
+        let block = Block::section(Text::mrkdwn("*hello*"))
+            .with_block_id("greeting")
+            .with_accessory(Element::image("https://github.com/octocat.png", "octocat"));
+
+        assert_eq!(
+            serde_json::json!({
+                "type": "section",
+                "block_id": "greeting",
+                "text": {"type": "mrkdwn", "text": "*hello*"},
+                "accessory": {"type": "image", "image_url": "https://github.com/octocat.png", "alt_text": "octocat"}
+            }),
+            serde_json::json!(block)
+        );
+    }
+
+    #[test]
+    fn test_serialize_blocks() {
+        let blocks = vec![
+            Block::header("Title"),
+            Block::divider(),
+            Block::Section { block_id: None, text: None, fields: Some(vec![Text::mrkdwn("*State*"), Text::plain("open")]), accessory: None },
+            Block::context(vec![
+                ContextElement::Image(Element::image("https://github.com/octocat.png", "octocat")),
+                ContextElement::Text(Text::mrkdwn("by octocat")),
+            ]),
+            Block::actions(vec![Element::button("close", "Close", "o/r#1").with_style(ButtonStyle::Danger)]),
+            Block::Image { block_id: None, image_url: "https://example.com/chart.png".to_string(), alt_text: "chart".to_string(), title: None },
+            Block::input("Title", Element::plain_text_input("title").with_initial_value("Bug").multiline()).optional(),
+            Block::RichText {
+                block_id: None,
+                elements: vec![RichTextElement::Section {
+                    elements: vec![
+                        RichTextInline::Text { text: "done".to_string(), style: Some(RichTextStyle { bold: true, ..Default::default() }) },
+                        RichTextInline::Emoji { name: "tada".to_string() },
+                    ],
+                }],
+            },
+        ];
+
+        assert_eq!(
+            serde_json::json!([
+                {"type": "header", "text": {"type": "plain_text", "text": "Title"}},
+                {"type": "divider"},
+                {"type": "section", "fields": [{"type": "mrkdwn", "text": "*State*"}, {"type": "plain_text", "text": "open"}]},
+                {"type": "context", "elements": [
+                    {"type": "image", "image_url": "https://github.com/octocat.png", "alt_text": "octocat"},
+                    {"type": "mrkdwn", "text": "by octocat"}
+                ]},
+                {"type": "actions", "elements": [
+                    {"type": "button", "action_id": "close", "text": {"type": "plain_text", "text": "Close"}, "value": "o/r#1", "style": "danger"}
+                ]},
+                {"type": "image", "image_url": "https://example.com/chart.png", "alt_text": "chart"},
+                {"type": "input", "label": {"type": "plain_text", "text": "Title"}, "element": {"type": "plain_text_input", "action_id": "title", "initial_value": "Bug", "multiline": true}, "optional": true},
+                {"type": "rich_text", "elements": [{"type": "rich_text_section", "elements": [
+                    {"type": "text", "text": "done", "style": {"bold": true}},
+                    {"type": "emoji", "name": "tada"}
+                ]}]}
+            ]),
+            serde_json::json!(blocks)
+        );
+
+        let deserialized: Vec<Block> = serde_json::from_value(serde_json::json!(blocks)).unwrap();
+
+        assert_eq!(blocks, deserialized);
+        assert_eq!(Ok(()), validate_blocks(&blocks, MAX_MESSAGE_BLOCKS));
+    }
+
+    #[test]
+    fn test_option_text() {
+        let option = OptionObject::new(&format!("org/{}", "r".repeat(100)), &format!("org/{}", "r".repeat(100)));
+
+        assert_eq!(MAX_OPTION_TEXT, option.text.text().chars().count());
+        assert!(option.text.text().ends_with('…'));
+        assert_eq!(104, option.value.len());
+    }
+
+    #[test]
+    fn test_validate_limits() {
+        assert!(Block::section(Text::mrkdwn(&"a".repeat(3000))).validate().is_ok());
+        assert!(Block::section(Text::mrkdwn(&"a".repeat(3001))).validate().is_err());
+        assert!(Block::header(&"a".repeat(151)).validate().is_err());
+        assert!(Block::Section { block_id: None, text: None, fields: Some(vec![Text::plain("a"); 11]), accessory: None }.validate().is_err());
+        assert!(Block::actions(vec![Element::button("a", "b", "c"); 26]).validate().is_err());
+        assert!(Block::actions(vec![Element::button("a", &"b".repeat(76), "c")]).validate().is_err());
+        assert!(Block::context(vec![ContextElement::Text(Text::plain("a")); 11]).validate().is_err());
+        assert!(Block::context(vec![ContextElement::Image(Element::button("a", "b", "c"))]).validate().is_err());
+        assert!(Block::Header { block_id: None, text: Text::mrkdwn("a") }.validate().is_err());
+        assert!(Block::Section { block_id: None, text: None, fields: None, accessory: None }.validate().is_err());
+        assert!(Block::divider().with_block_id(&"a".repeat(256)).validate().is_err());
+
+        let options = vec![OptionObject::new("bug", "bug"); 101];
+        assert!(Block::input("Labels", Element::multi_static_select("labels", "Select labels", options)).validate().is_err());
+
+        assert!(validate_blocks(&vec![Block::divider(); 50], MAX_MESSAGE_BLOCKS).is_ok());
+        assert!(validate_blocks(&vec![Block::divider(); 51], MAX_MESSAGE_BLOCKS).is_err());
+        assert!(validate_blocks(&vec![Block::divider(); 51], MAX_VIEW_BLOCKS).is_ok());
+    }
+}
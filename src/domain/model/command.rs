@@ -0,0 +1,268 @@
+use regex::Regex;
+
+use crate::domain::model::{
+    digest::Frequency,
+    filter::Rule,
+    github::IssueReference,
+    quiet_hours::QuietHours,
+    routing::{RouteScope, ROUTABLE_EVENTS},
+    subscription::Category,
+};
+
+#[derive(PartialEq, Debug)]
+pub enum Command {
+    Lookup(IssueReference),
+    AutoExpand(bool),
+    /// `link <github-login>` verifies the login through GitHub; a bare `link` tries to match by email.
+    Link(Option<String>),
+    DirectMessages(bool),
+    /// `route org/repo [event]` sends events to the current channel, `route default` makes it the fallback.
+    Route(RouteScope),
+    Unroute(RouteScope),
+    Routes,
+    /// `share org/repo` lets the current channel show a private repository, `unshare org/repo` revokes it.
+    Share(String),
+    Unshare(String),
+    /// `subscribe org/repo [issues,pulls,...]`; without categories the channel gets all of them.
+    Subscribe {
+        repository: String,
+        categories: Vec<Category>,
+    },
+    /// Without categories the whole subscription to the repository is dropped.
+    Unsubscribe {
+        repository: String,
+        categories: Vec<Category>,
+    },
+    Subscriptions,
+    /// `filter org/repo +label:bug -draft:true` adds rules to a subscription, `filter org/repo clear` drops them.
+    Filter {
+        repository: String,
+        rules: Vec<Rule>,
+    },
+    /// `digest org/repo daily|weekly` summarises a subscription periodically, `digest org/repo off`
+    /// goes back to real-time delivery.
+    Digest {
+        repository: String,
+        frequency: Option<Frequency>,
+    },
+    /// `quiet 22:00-07:00 Europe/Berlin` holds non-urgent events overnight, `quiet off` ends it.
+    /// The timezone defaults to UTC.
+    QuietHours(Option<QuietHours>),
+    /// A known command whose arguments are wrong, with what to fix.
+    Invalid(String),
+}
+
+impl Command {
+    /// Parses the text given to the slash command or to a mention of the bot.
+    /// `org/repo#12`, `status org/repo#12` and `what's org/repo#12?` all resolve to a lookup.
+    pub fn parse(text: &str) -> Option<Command> {
+        let mut words = text.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some("autoexpand"), Some("on")) => Some(Command::AutoExpand(true)),
+            (Some("autoexpand"), Some("off")) => Some(Command::AutoExpand(false)),
+            (Some("dm"), Some("on")) => Some(Command::DirectMessages(true)),
+            (Some("dm"), Some("off")) => Some(Command::DirectMessages(false)),
+            (Some("link"), None) => Some(Command::Link(None)),
+            (Some("link"), Some(login)) if Self::is_github_login(login) => Some(Command::Link(Some(login.to_string()))),
+            (Some("route"), Some(target)) => Self::parse_route_scope(target, words.next()).map(Command::Route),
+            (Some("unroute"), Some(target)) => Self::parse_route_scope(target, words.next()).map(Command::Unroute),
+            (Some("routes"), None) => Some(Command::Routes),
+            (Some("share"), Some(repository)) if Self::is_repository(repository) => Some(Command::Share(repository.to_string())),
+            (Some("unshare"), Some(repository)) if Self::is_repository(repository) => Some(Command::Unshare(repository.to_string())),
+            (Some("subscribe"), Some(repository)) if Self::is_repository(repository) => Some(Command::Subscribe {
+                repository: repository.to_string(),
+                categories: Self::parse_categories(words)?,
+            }),
+            (Some("unsubscribe"), Some(repository)) if Self::is_repository(repository) => Some(Command::Unsubscribe {
+                repository: repository.to_string(),
+                categories: Self::parse_categories(words)?,
+            }),
+            (Some("subscriptions"), None) => Some(Command::Subscriptions),
+            (Some("filter"), Some(repository)) if Self::is_repository(repository) => Some(Command::Filter {
+                repository: repository.to_string(),
+                rules: Self::parse_rules(words)?,
+            }),
+            (Some("digest"), Some(repository)) if Self::is_repository(repository) => {
+                let frequency = match words.next()? {
+                    "off" => None,
+                    name => Some(Frequency::parse(name)?),
+                };
+
+                Some(Command::Digest { repository: repository.to_string(), frequency })
+            },
+            (Some("quiet"), Some("off")) => Some(Command::QuietHours(None)),
+            (Some("quiet"), Some(window)) => match QuietHours::parse(window, words.next().unwrap_or("UTC")) {
+                Ok(quiet_hours) => Some(Command::QuietHours(Some(quiet_hours))),
+                Err(e) => Some(Command::Invalid(e)),
+            },
+            _ => IssueReference::parse(text).map(Command::Lookup),
+        }
+    }
+
+    fn parse_route_scope(target: &str, event: Option<&str>) -> Option<RouteScope> {
+        match (target, event) {
+            ("default", None) => Some(RouteScope::Default),
+            (repository, event) if Self::is_repository(repository) && event.map_or(true, |e| ROUTABLE_EVENTS.contains(&e)) => Some(RouteScope::Repository {
+                repository: repository.to_string(),
+                event: event.map(str::to_string),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Accepts `issues,pulls` as well as `issues, pulls`; any unknown category rejects the command.
+    fn parse_categories<'a>(words: impl Iterator<Item = &'a str>) -> Option<Vec<Category>> {
+        words
+            .flat_map(|word| word.split(','))
+            .filter(|name| !name.is_empty())
+            .map(Category::parse)
+            .collect()
+    }
+
+    /// `clear` alone stands for no rules; any rule that does not parse rejects the command.
+    fn parse_rules<'a>(words: impl Iterator<Item = &'a str>) -> Option<Vec<Rule>> {
+        let words: Vec<&str> = words.collect();
+
+        match words.as_slice() {
+            [] => None,
+            ["clear"] => Some(vec![]),
+            words => words.iter().map(|word| Rule::parse(word)).collect(),
+        }
+    }
+
+    fn is_repository(name: &str) -> bool {
+        let re = Regex::new(r"^[\w.-]+/[\w.-]+$").unwrap();
+
+        re.is_match(name)
+    }
+
+    fn is_github_login(login: &str) -> bool {
+        let re = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9-]{0,38}$").unwrap();
+
+        re.is_match(login)
+    }
+}
+
+#[cfg(test)]
+mod command_test {
+    use super::*;
+
+    #[test]
+    fn test_parse_lookup() {
+        let reference = IssueReference {
+            owner: "org".to_string(),
+            repo: "repo".to_string(),
+            number: 12,
+        };
+
+        assert_eq!(Some(Command::Lookup(reference.clone())), Command::parse("org/repo#12"));
+        assert_eq!(Some(Command::Lookup(reference.clone())), Command::parse("status org/repo#12"));
+        assert_eq!(Some(Command::Lookup(reference)), Command::parse("what's org/repo#12?"));
+    }
+
+    #[test]
+    fn test_parse_auto_expand() {
+        assert_eq!(Some(Command::AutoExpand(true)), Command::parse("autoexpand on"));
+        assert_eq!(Some(Command::AutoExpand(false)), Command::parse(" autoexpand off "));
+        assert_eq!(None, Command::parse("autoexpand"));
+    }
+
+    #[test]
+    fn test_parse_direct_messages() {
+        assert_eq!(Some(Command::DirectMessages(true)), Command::parse("dm on"));
+        assert_eq!(Some(Command::DirectMessages(false)), Command::parse("dm off"));
+        assert_eq!(None, Command::parse("dm"));
+    }
+
+    #[test]
+    fn test_parse_link() {
+        assert_eq!(Some(Command::Link(Some("octocat".to_string()))), Command::parse("link octocat"));
+        assert_eq!(Some(Command::Link(None)), Command::parse("link"));
+        assert_eq!(None, Command::parse("link <@U123ABC456>"));
+    }
+
+    #[test]
+    fn test_parse_route() {
+        let scope = |event: Option<&str>| RouteScope::Repository {
+            repository: "org/api".to_string(),
+            event: event.map(str::to_string),
+        };
+
+        assert_eq!(Some(Command::Route(scope(None))), Command::parse("route org/api"));
+        assert_eq!(Some(Command::Route(scope(Some("pull_request")))), Command::parse("route org/api pull_request"));
+        assert_eq!(Some(Command::Route(RouteScope::Default)), Command::parse("route default"));
+        assert_eq!(Some(Command::Unroute(scope(Some("issues")))), Command::parse("unroute org/api issues"));
+        assert_eq!(Some(Command::Routes), Command::parse("routes"));
+        assert_eq!(None, Command::parse("route org/api pushes"));
+        assert_eq!(None, Command::parse("route api"));
+    }
+
+    #[test]
+    fn test_parse_share() {
+        assert_eq!(Some(Command::Share("org/api".to_string())), Command::parse("share org/api"));
+        assert_eq!(Some(Command::Unshare("org/api".to_string())), Command::parse("unshare org/api"));
+        assert_eq!(None, Command::parse("share api"));
+    }
+
+    #[test]
+    fn test_parse_subscribe() {
+        assert_eq!(
+            Some(Command::Subscribe { repository: "org/api".to_string(), categories: vec![Category::Issues, Category::Pulls] }),
+            Command::parse("subscribe org/api issues, pulls")
+        );
+        assert_eq!(
+            Some(Command::Subscribe { repository: "org/api".to_string(), categories: vec![] }),
+            Command::parse("subscribe org/api")
+        );
+        assert_eq!(
+            Some(Command::Unsubscribe { repository: "org/api".to_string(), categories: vec![Category::Reviews] }),
+            Command::parse("unsubscribe org/api reviews")
+        );
+        assert_eq!(Some(Command::Subscriptions), Command::parse("subscriptions"));
+        assert_eq!(None, Command::parse("subscribe org/api issues,pushes"));
+        assert_eq!(None, Command::parse("subscribe api"));
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            Some(Command::Filter {
+                repository: "org/api".to_string(),
+                rules: vec![Rule::parse("+path:infra/**").unwrap(), Rule::parse("-draft:true").unwrap()],
+            }),
+            Command::parse("filter org/api +path:infra/** -draft:true")
+        );
+        assert_eq!(Some(Command::Filter { repository: "org/api".to_string(), rules: vec![] }), Command::parse("filter org/api clear"));
+        assert_eq!(None, Command::parse("filter org/api"));
+        assert_eq!(None, Command::parse("filter org/api +milestone:v1"));
+    }
+
+    #[test]
+    fn test_parse_digest() {
+        assert_eq!(
+            Some(Command::Digest { repository: "org/api".to_string(), frequency: Some(Frequency::Weekly) }),
+            Command::parse("digest org/api weekly")
+        );
+        assert_eq!(Some(Command::Digest { repository: "org/api".to_string(), frequency: None }), Command::parse("digest org/api off"));
+        assert_eq!(None, Command::parse("digest org/api"));
+        assert_eq!(None, Command::parse("digest org/api hourly"));
+    }
+
+    #[test]
+    fn test_parse_quiet_hours() {
+        assert_eq!(
+            Some(Command::QuietHours(QuietHours::parse("22:00-07:00", "Asia/Tokyo").ok())),
+            Command::parse("quiet 22:00-07:00 Asia/Tokyo")
+        );
+        assert_eq!(Some(Command::QuietHours(QuietHours::parse("22:00-07:00", "UTC").ok())), Command::parse("quiet 22:00-07:00"));
+        assert_eq!(Some(Command::QuietHours(None)), Command::parse("quiet off"));
+        assert_eq!(Some(Command::Invalid("`25:00-07:00` is not a window like `22:00-07:00`".to_string())), Command::parse("quiet 25:00-07:00"));
+        assert!(matches!(Command::parse("quiet 22:00-07:00 Berlin"), Some(Command::Invalid(_))));
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert_eq!(None, Command::parse("hello"));
+    }
+}
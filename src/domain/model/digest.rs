@@ -0,0 +1,151 @@
+use std::fmt;
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
+use serde::{Serialize, Deserialize};
+
+use crate::domain::model::rollup::{is_bot, ItemKind, RollupEntry};
+
+/// How often a subscription in digest mode is summarised instead of streamed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+}
+
+impl Frequency {
+    pub fn parse(name: &str) -> Option<Frequency> {
+        match name {
+            "daily" => Some(Frequency::Daily),
+            "weekly" => Some(Frequency::Weekly),
+            _ => None,
+        }
+    }
+
+    /// Daily digests go out at `hour` UTC, weekly ones at the same hour on Mondays.
+    pub fn is_due(&self, now: DateTime<Utc>, hour: u32) -> bool {
+        match self {
+            Frequency::Daily => now.hour() == hour,
+            Frequency::Weekly => now.hour() == hour && now.weekday() == Weekday::Mon,
+        }
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frequency::Daily => write!(f, "daily"),
+            Frequency::Weekly => write!(f, "weekly"),
+        }
+    }
+}
+
+/// What a digest reports, compiled from the events collected since the previous one.
+#[derive(PartialEq, Debug)]
+pub struct Digest<'a> {
+    pub opened_issues: Vec<&'a RollupEntry>,
+    pub closed_issues: Vec<&'a RollupEntry>,
+    pub merged_pulls: Vec<&'a RollupEntry>,
+    pub releases: Vec<&'a RollupEntry>,
+    /// Logins with their number of events, most active first. Bots are left out.
+    pub top_contributors: Vec<(&'a str, usize)>,
+}
+
+/// Contributors beyond this are left out of a digest.
+const TOP_CONTRIBUTORS: usize = 5;
+
+impl<'a> Digest<'a> {
+    pub fn compile(entries: &'a [RollupEntry]) -> Digest<'a> {
+        let matching = |kind: ItemKind, action: &str| -> Vec<&'a RollupEntry> {
+            let mut matching: Vec<&RollupEntry> = vec![];
+
+            // An item reopened and closed again within the period is still listed once.
+            for entry in entries.iter().filter(|entry| entry.kind == kind && entry.action == action) {
+                if !matching.iter().any(|other| entry.number.is_some() && other.repository == entry.repository && other.number == entry.number) {
+                    matching.push(entry);
+                }
+            }
+
+            matching
+        };
+
+        let mut top_contributors: Vec<(&str, usize)> = vec![];
+
+        for login in entries.iter().map(|entry| entry.actor.as_deref().unwrap_or(&entry.author)).filter(|login| !is_bot(login, &[])) {
+            match top_contributors.iter_mut().find(|(other, _)| *other == login) {
+                Some((_, count)) => *count += 1,
+                None => top_contributors.push((login, 1)),
+            }
+        }
+
+        top_contributors.sort_by(|a, b| b.1.cmp(&a.1));
+        top_contributors.truncate(TOP_CONTRIBUTORS);
+
+        Digest {
+            opened_issues: matching(ItemKind::Issue, "opened"),
+            closed_issues: matching(ItemKind::Issue, "closed"),
+            merged_pulls: matching(ItemKind::PullRequest, "merged"),
+            releases: matching(ItemKind::Release, "published"),
+            top_contributors,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.opened_issues.is_empty() && self.closed_issues.is_empty() && self.merged_pulls.is_empty() && self.releases.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod digest_test {
+    use super::*;
+
+    fn entry(kind: ItemKind, number: Option<u32>, action: &str, actor: &str) -> RollupEntry {
+        RollupEntry {
+            repository: "org/api".to_string(),
+            number,
+            title: "t".to_string(),
+            html_url: format!("https://github.com/org/api/issues/{}", number.unwrap_or_default()),
+            author: "octocat".to_string(),
+            actor: Some(actor.to_string()),
+            kind,
+            action: action.to_string(),
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_frequency() {
+        assert_eq!(Some(Frequency::Weekly), Frequency::parse("weekly"));
+        assert_eq!(None, Frequency::parse("hourly"));
+        // 2024-07-08 is a Monday.
+        assert!(Frequency::Daily.is_due(at("2024-07-09T09:00:00Z"), 9));
+        assert!(!Frequency::Daily.is_due(at("2024-07-09T10:00:00Z"), 9));
+        assert!(Frequency::Weekly.is_due(at("2024-07-08T09:00:00Z"), 9));
+        assert!(!Frequency::Weekly.is_due(at("2024-07-09T09:00:00Z"), 9));
+    }
+
+    #[test]
+    fn test_compile() {
+        let entries = vec![
+            entry(ItemKind::Issue, Some(1), "opened", "monalisa"),
+            entry(ItemKind::Issue, Some(1), "closed", "hubot"),
+            entry(ItemKind::Issue, Some(1), "reopened", "monalisa"),
+            entry(ItemKind::Issue, Some(1), "closed", "hubot"),
+            entry(ItemKind::PullRequest, Some(2), "opened", "monalisa"),
+            entry(ItemKind::PullRequest, Some(2), "merged", "hubot"),
+            entry(ItemKind::Release, None, "published", "hubot"),
+            entry(ItemKind::Release, None, "published", "github-actions[bot]"),
+        ];
+        let digest = Digest::compile(&entries);
+
+        assert_eq!(vec![&entries[0]], digest.opened_issues);
+        assert_eq!(vec![&entries[1]], digest.closed_issues);
+        assert_eq!(vec![&entries[5]], digest.merged_pulls);
+        assert_eq!(vec![&entries[6], &entries[7]], digest.releases);
+        assert_eq!(vec![("hubot", 4), ("monalisa", 3)], digest.top_contributors);
+        assert!(!digest.is_empty());
+        assert!(Digest::compile(&entries[4..5]).is_empty());
+    }
+}
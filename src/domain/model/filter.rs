@@ -0,0 +1,266 @@
+use std::{fmt, mem};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+/// Rules narrowing what a subscription delivers. Excludes win over includes; includes on the same
+/// predicate are alternatives, includes on different predicates must all hold. Issues have no base,
+/// draft status or paths, so includes on those only narrow down pull request events.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Filter {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Rule {
+    pub effect: Effect,
+    pub predicate: Predicate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    Include,
+    Exclude,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    Action(String),
+    Label(String),
+    Author(String),
+    /// Glob over the base branch of a pull request, like `release/*`.
+    Base(String),
+    Draft(bool),
+    /// Glob over the files a pull request changes, like `infra/**`.
+    Path(String),
+}
+
+/// The parts of a webhook event the predicates look at. Issues have no base, draft status or paths.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FilterEvent {
+    /// Whether the event is about a pull request, including comments made on one.
+    pub pull_request: bool,
+    pub action: String,
+    pub author: Option<String>,
+    pub labels: Vec<String>,
+    pub base: Option<String>,
+    pub draft: Option<bool>,
+    pub paths: Vec<String>,
+}
+
+impl Filter {
+    pub fn allows(&self, event: &FilterEvent) -> bool {
+        let (includes, excludes): (Vec<&Rule>, Vec<&Rule>) = self.rules.iter().partition(|rule| rule.effect == Effect::Include);
+
+        if excludes.iter().any(|rule| rule.predicate.matches(event)) {
+            return false;
+        }
+
+        let includes: Vec<&Rule> = includes.into_iter().filter(|rule| event.pull_request || !rule.predicate.is_pull_request_only()).collect();
+
+        includes.iter().all(|rule| {
+            includes
+                .iter()
+                .filter(|other| mem::discriminant(&other.predicate) == mem::discriminant(&rule.predicate))
+                .any(|other| other.predicate.matches(event))
+        })
+    }
+
+    /// Changed paths cost an extra GitHub request, so they are only fetched for filters that need them.
+    pub fn needs_paths(&self) -> bool {
+        self.rules.iter().any(|rule| matches!(rule.predicate, Predicate::Path(_)))
+    }
+
+    /// Comments on pull requests come with the issue side of it only, without base or draft status.
+    pub fn needs_pull_request(&self) -> bool {
+        self.rules.iter().any(|rule| matches!(rule.predicate, Predicate::Base(_) | Predicate::Draft(_)))
+    }
+}
+
+impl Rule {
+    /// Parses `+label:bug`, `-author:dependabot[bot]` or `path:infra/**`, which includes like `+`.
+    pub fn parse(text: &str) -> Option<Rule> {
+        let (effect, rest) = match text.split_at(text.len().min(1)) {
+            ("+", rest) => (Effect::Include, rest),
+            ("-", rest) => (Effect::Exclude, rest),
+            _ => (Effect::Include, text),
+        };
+        let (name, value) = rest.split_once(':').filter(|(_, value)| !value.is_empty())?;
+
+        let predicate = match name {
+            "action" => Predicate::Action(value.to_string()),
+            "label" => Predicate::Label(value.to_string()),
+            "author" => Predicate::Author(value.to_string()),
+            "base" => Predicate::Base(value.to_string()),
+            "draft" => Predicate::Draft(value.parse().ok()?),
+            "path" => Predicate::Path(value.to_string()),
+            _ => return None,
+        };
+
+        Some(Rule { effect, predicate })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = match self.effect {
+            Effect::Include => "+",
+            Effect::Exclude => "-",
+        };
+
+        match &self.predicate {
+            Predicate::Action(action) => write!(f, "{}action:{}", sign, action),
+            Predicate::Label(label) => write!(f, "{}label:{}", sign, label),
+            Predicate::Author(login) => write!(f, "{}author:{}", sign, login),
+            Predicate::Base(branch) => write!(f, "{}base:{}", sign, branch),
+            Predicate::Draft(draft) => write!(f, "{}draft:{}", sign, draft),
+            Predicate::Path(path) => write!(f, "{}path:{}", sign, path),
+        }
+    }
+}
+
+impl Predicate {
+    pub fn is_pull_request_only(&self) -> bool {
+        matches!(self, Predicate::Base(_) | Predicate::Draft(_) | Predicate::Path(_))
+    }
+
+    pub fn matches(&self, event: &FilterEvent) -> bool {
+        match self {
+            Predicate::Action(action) => &event.action == action,
+            Predicate::Label(label) => event.labels.iter().any(|name| name.eq_ignore_ascii_case(label)),
+            Predicate::Author(login) => event.author.as_deref().is_some_and(|author| author.eq_ignore_ascii_case(login)),
+            Predicate::Base(branch) => event.base.as_deref().is_some_and(|base| glob(branch).is_match(base)),
+            Predicate::Draft(draft) => event.draft == Some(*draft),
+            Predicate::Path(path) => {
+                let re = glob(path);
+
+                event.paths.iter().any(|changed| re.is_match(changed))
+            },
+        }
+    }
+}
+
+/// `**` spans directories, `*` and `?` stay within one path segment.
+fn glob(pattern: &str) -> Regex {
+    let mut re = String::from("^");
+    let mut rest = pattern;
+
+    while let Some(c) = rest.chars().next() {
+        let (part, len) = if rest.starts_with("**/") {
+            ("(.*/)?".to_string(), 3)
+        } else if rest.starts_with("**") {
+            (".*".to_string(), 2)
+        } else {
+            match c {
+                '*' => ("[^/]*".to_string(), 1),
+                '?' => ("[^/]".to_string(), 1),
+                _ => (regex::escape(&c.to_string()), c.len_utf8()),
+            }
+        };
+
+        re.push_str(&part);
+        rest = &rest[len..];
+    }
+
+    re.push('$');
+
+    Regex::new(&re).unwrap()
+}
+
+#[cfg(test)]
+mod filter_test {
+    use super::*;
+
+    fn event() -> FilterEvent {
+        FilterEvent {
+            pull_request: true,
+            action: "opened".to_string(),
+            author: Some("octocat".to_string()),
+            labels: vec!["bug".to_string(), "infra".to_string()],
+            base: Some("release/1.2".to_string()),
+            draft: Some(false),
+            paths: vec!["infra/terraform/main.tf".to_string(), "README.md".to_string()],
+        }
+    }
+
+    fn filter(rules: &[&str]) -> Filter {
+        Filter { rules: rules.iter().map(|rule| Rule::parse(rule).unwrap()).collect() }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(Some(Rule { effect: Effect::Include, predicate: Predicate::Label("bug".to_string()) }), Rule::parse("+label:bug"));
+        assert_eq!(Some(Rule { effect: Effect::Include, predicate: Predicate::Path("infra/**".to_string()) }), Rule::parse("path:infra/**"));
+        assert_eq!(Some(Rule { effect: Effect::Exclude, predicate: Predicate::Draft(true) }), Rule::parse("-draft:true"));
+        assert_eq!(None, Rule::parse("-draft:maybe"));
+        assert_eq!(None, Rule::parse("+milestone:v1"));
+        assert_eq!(None, Rule::parse("+label:"));
+        assert_eq!("-author:dependabot[bot]", Rule::parse("-author:dependabot[bot]").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_action() {
+        assert!(Predicate::Action("opened".to_string()).matches(&event()));
+        assert!(!Predicate::Action("closed".to_string()).matches(&event()));
+    }
+
+    #[test]
+    fn test_label() {
+        assert!(Predicate::Label("Bug".to_string()).matches(&event()));
+        assert!(!Predicate::Label("docs".to_string()).matches(&event()));
+    }
+
+    #[test]
+    fn test_author() {
+        assert!(Predicate::Author("OctoCat".to_string()).matches(&event()));
+        assert!(!Predicate::Author("renovate[bot]".to_string()).matches(&event()));
+        assert!(!Predicate::Author("octocat".to_string()).matches(&FilterEvent::default()));
+    }
+
+    #[test]
+    fn test_base() {
+        assert!(Predicate::Base("release/*".to_string()).matches(&event()));
+        assert!(!Predicate::Base("main".to_string()).matches(&event()));
+        assert!(!Predicate::Base("*".to_string()).matches(&event()));
+        assert!(!Predicate::Base("main".to_string()).matches(&FilterEvent::default()));
+    }
+
+    #[test]
+    fn test_draft() {
+        assert!(Predicate::Draft(false).matches(&event()));
+        assert!(!Predicate::Draft(true).matches(&event()));
+        assert!(!Predicate::Draft(false).matches(&FilterEvent::default()));
+    }
+
+    #[test]
+    fn test_path() {
+        assert!(Predicate::Path("infra/**".to_string()).matches(&event()));
+        assert!(Predicate::Path("**/*.tf".to_string()).matches(&event()));
+        assert!(Predicate::Path("*.md".to_string()).matches(&event()));
+        assert!(!Predicate::Path("infra/*.tf".to_string()).matches(&event()));
+        assert!(!Predicate::Path("docs/**".to_string()).matches(&event()));
+    }
+
+    #[test]
+    fn test_allows() {
+        assert!(Filter::default().allows(&event()));
+        assert!(filter(&["+label:docs", "+label:infra"]).allows(&event()));
+        assert!(!filter(&["+label:infra", "+path:docs/**"]).allows(&event()));
+        assert!(!filter(&["+path:infra/**", "-author:octocat"]).allows(&event()));
+        assert!(filter(&["-draft:true"]).allows(&event()));
+        assert!(filter(&["+path:infra/**"]).needs_paths());
+        assert!(!filter(&["-draft:true"]).needs_paths());
+        assert!(filter(&["+base:main"]).needs_pull_request());
+    }
+
+    #[test]
+    fn test_allows_issue() {
+        let issue = FilterEvent { pull_request: false, base: None, draft: None, paths: vec![], ..event() };
+
+        assert!(filter(&["+base:main", "+path:docs/**", "+draft:true"]).allows(&issue));
+        assert!(filter(&["+path:docs/**", "+label:bug"]).allows(&issue));
+        assert!(!filter(&["+path:docs/**", "+label:docs"]).allows(&issue));
+    }
+}
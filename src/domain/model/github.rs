@@ -1,13 +1,21 @@
+use std::fmt;
+use regex::Regex;
 use serde::{Serialize, Deserialize};
 
 pub trait PayloadRepository {
     fn html_url(&self) -> &str;
     fn title(&self) -> &str;
     fn body(&self) -> Option<&str>;
-    fn state(&self) -> &str;
+    fn state(&self) -> State;
+    fn card_state(&self) -> CardState;
+    fn labels(&self) -> &[Label];
     fn created_at(&self) -> &str;
     fn number(&self) -> u32;
     fn user(&self) -> &User;
+
+    fn merged_by(&self) -> Option<&User> {
+        None
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -15,10 +23,22 @@ pub struct Issue {
   pub html_url: String,
   pub title: String,
   pub body: Option<String>,
-  pub state: String,
+  pub state: State,
+  #[serde(default)]
+  pub state_reason: Option<StateReason>,
+  #[serde(default)]
+  pub labels: Vec<Label>,
   pub created_at: String,
   pub number: u32,
   pub user: User,
+  /// Present when the issue is a pull request, as in `issue_comment` events on pull requests.
+  #[serde(default)]
+  pub pull_request: Option<IssuePullRequest>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IssuePullRequest {
+  pub html_url: String,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -26,10 +46,93 @@ pub struct PullRequest {
   pub html_url: String,
   pub title: String,
   pub body: Option<String>,
-  pub state: String,
+  pub state: State,
+  #[serde(default)]
+  pub draft: bool,
+  #[serde(default)]
+  pub labels: Vec<Label>,
   pub created_at: String,
   pub number: u32,
   pub user: User,
+  #[serde(default)]
+  pub merged: bool,
+  pub merged_by: Option<User>,
+  #[serde(default)]
+  pub base: Option<BranchRef>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    Open,
+    Closed,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Open => write!(f, "open"),
+            State::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+/// Why an issue is in its state; GitHub only reports it for issues.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StateReason {
+    Completed,
+    NotPlanned,
+    Reopened,
+}
+
+/// The state a card shows, combining `State` with how the item got there.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CardState {
+    Opened,
+    Reopened,
+    Draft,
+    ClosedCompleted,
+    ClosedNotPlanned,
+    /// A pull request closed without being merged.
+    Closed,
+    Merged,
+}
+
+impl CardState {
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            CardState::Opened => ":large_green_circle:",
+            CardState::Reopened => ":arrows_counterclockwise:",
+            CardState::Draft => ":memo:",
+            CardState::ClosedCompleted => ":white_check_mark:",
+            CardState::ClosedNotPlanned => ":no_entry_sign:",
+            CardState::Closed => ":no_entry:",
+            CardState::Merged => ":tada:",
+        }
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            CardState::Opened => "Open",
+            CardState::Reopened => "Reopened",
+            CardState::Draft => "Draft",
+            CardState::ClosedCompleted => "Closed as completed",
+            CardState::ClosedNotPlanned => "Closed as not planned",
+            CardState::Closed => "Closed",
+            CardState::Merged => "Merged",
+        }
+    }
+
+    /// The colour bar of the card, following the colours GitHub uses for the same states.
+    pub fn colour(&self) -> &'static str {
+        match self {
+            CardState::Opened | CardState::Reopened => "#1f883d",
+            CardState::Draft | CardState::ClosedNotPlanned => "#59636e",
+            CardState::ClosedCompleted | CardState::Merged => "#8250df",
+            CardState::Closed => "#cf222e",
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -52,8 +155,21 @@ impl PayloadRepository for Issue {
         self.body.as_deref()
     }
 
-    fn state(&self) -> &str {
-        &self.state
+    fn state(&self) -> State {
+        self.state
+    }
+
+    fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    fn card_state(&self) -> CardState {
+        match (self.state, self.state_reason) {
+            (State::Open, Some(StateReason::Reopened)) => CardState::Reopened,
+            (State::Open, _) => CardState::Opened,
+            (State::Closed, Some(StateReason::NotPlanned)) => CardState::ClosedNotPlanned,
+            (State::Closed, _) => CardState::ClosedCompleted,
+        }
     }
 
     fn created_at(&self) -> &str {
@@ -82,8 +198,22 @@ impl PayloadRepository for PullRequest {
         self.body.as_deref()
     }
 
-    fn state(&self) -> &str {
-        &self.state
+    fn state(&self) -> State {
+        self.state
+    }
+
+    fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// Pull requests closed without merging show as closed, set apart from merged ones.
+    fn card_state(&self) -> CardState {
+        match (self.state, self.merged, self.draft) {
+            (State::Closed, true, _) => CardState::Merged,
+            (State::Closed, false, _) => CardState::Closed,
+            (State::Open, _, true) => CardState::Draft,
+            (State::Open, _, false) => CardState::Opened,
+        }
     }
 
     fn created_at(&self) -> &str {
@@ -97,4 +227,410 @@ impl PayloadRepository for PullRequest {
     fn user(&self) -> &User {
         &self.user
     }
+
+    fn merged_by(&self) -> Option<&User> {
+        self.merged_by.as_ref().filter(|_| self.merged)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UserSearchResult {
+  pub items: Vec<User>,
+}
+
+#[derive(Deserialize)]
+pub struct OAuthTokenResponse {
+  pub access_token: Option<String>,
+  pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Commit {
+  pub sha: String,
+  pub html_url: String,
+  pub commit: CommitDetail,
+  pub author: Option<User>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CommitDetail {
+  pub message: String,
+  pub author: CommitAuthor,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CommitAuthor {
+  pub name: String,
+  pub date: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Repository {
+  pub full_name: String,
+  pub private: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+  pub total_count: u32,
+  pub items: Vec<SearchItem>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchItem {
+  pub html_url: String,
+  pub title: String,
+  pub number: u32,
+  pub repository_url: String,
+}
+
+impl SearchItem {
+    pub fn reference(&self) -> IssueReference {
+        let mut segments = self.repository_url.rsplit('/');
+        let repo = segments.next().unwrap_or_default().to_string();
+        let owner = segments.next().unwrap_or_default().to_string();
+
+        IssueReference { owner, repo, number: self.number }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PullRequestHead {
+  pub head: CommitRef,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CommitRef {
+  pub sha: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BranchRef {
+  #[serde(rename = "ref")]
+  pub name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PullRequestFile {
+  pub filename: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CheckRuns {
+  pub check_runs: Vec<CheckRun>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CheckRun {
+  pub status: String,
+  pub conclusion: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CiStatus {
+    Success,
+    Failure,
+    Pending,
+    Unknown,
+}
+
+impl CiStatus {
+    pub fn from_check_runs(check_runs: &[CheckRun]) -> CiStatus {
+        if check_runs.is_empty() {
+            return CiStatus::Unknown;
+        }
+
+        let failed = check_runs.iter().any(|run| {
+            matches!(run.conclusion.as_deref(), Some("failure" | "cancelled" | "timed_out" | "action_required" | "startup_failure"))
+        });
+
+        if failed {
+            CiStatus::Failure
+        } else if check_runs.iter().any(|run| run.status != "completed") {
+            CiStatus::Pending
+        } else {
+            CiStatus::Success
+        }
+    }
+
+    pub fn emoji(&self) -> &str {
+        match self {
+            CiStatus::Success => ":white_check_mark:",
+            CiStatus::Failure => ":x:",
+            CiStatus::Pending => ":hourglass_flowing_sand:",
+            CiStatus::Unknown => ":grey_question:",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct NewIssue {
+  pub title: String,
+  pub body: String,
+  pub labels: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Label {
+  pub name: String,
+  /// Hex colour without the leading `#`.
+  #[serde(default)]
+  pub color: String,
+}
+
+impl Label {
+    /// The coloured circle emoji closest to the label colour, since Slack text cannot be coloured.
+    pub fn emoji(&self) -> &'static str {
+        let channel = |i: usize| self.color.get(i..i + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        let (r, g, b) = match (channel(0), channel(2), channel(4)) {
+            (Some(r), Some(g), Some(b)) if self.color.len() == 6 => (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0),
+            _ => return ":white_circle:",
+        };
+
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+
+        if delta < 0.15 {
+            return if max < 0.5 { ":black_circle:" } else { ":white_circle:" };
+        }
+
+        let hue = if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+
+        match hue {
+            h if !(15.0..345.0).contains(&h) => ":red_circle:",
+            h if h < 45.0 && max < 0.6 => ":large_brown_circle:",
+            h if h < 45.0 => ":large_orange_circle:",
+            h if h < 70.0 => ":large_yellow_circle:",
+            h if h < 170.0 => ":large_green_circle:",
+            h if h < 245.0 => ":large_blue_circle:",
+            _ => ":large_purple_circle:",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RepositoryPermission {
+  pub permission: String,
+}
+
+impl RepositoryPermission {
+    pub fn can_write(&self) -> bool {
+        matches!(self.permission.as_str(), "admin" | "write")
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct IssueReference {
+    pub owner: String,
+    pub repo: String,
+    pub number: u32,
+}
+
+impl IssueReference {
+    pub fn parse_all(text: &str) -> Vec<IssueReference> {
+        let re = Regex::new(r"(?P<owner>[\w.-]+)\/(?P<repo>[\w.-]+)\#(?P<issue_number>\d+)").unwrap();
+
+        re.captures_iter(text)
+            .filter_map(|caps| Some(IssueReference {
+                owner: caps["owner"].to_string(),
+                repo: caps["repo"].to_string(),
+                number: caps["issue_number"].parse().ok()?,
+            }))
+            .collect()
+    }
+
+    pub fn parse(text: &str) -> Option<IssueReference> {
+        Self::parse_all(text).pop()
+    }
+}
+
+impl fmt::Display for IssueReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}#{}", self.owner, self.repo, self.number)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GithubLink {
+    Issue(IssueReference),
+    PullRequest(IssueReference),
+    Commit {
+        owner: String,
+        repo: String,
+        sha: String,
+    },
+}
+
+impl GithubLink {
+    pub fn parse(url: &str) -> Option<GithubLink> {
+        let re = Regex::new(r"^https?://(?:www\.)?github\.com/(?P<owner>[\w.-]+)/(?P<repo>[\w.-]+)/(?P<kind>issues|pull|commit)/(?P<id>[0-9a-fA-F]+)").unwrap();
+
+        let caps = re.captures(url)?;
+        let owner = caps["owner"].to_string();
+        let repo = caps["repo"].to_string();
+
+        match &caps["kind"] {
+            "commit" => Some(GithubLink::Commit { owner, repo, sha: caps["id"].to_string() }),
+            kind => {
+                let reference = IssueReference { owner, repo, number: caps["id"].parse().ok()? };
+
+                match kind {
+                    "issues" => Some(GithubLink::Issue(reference)),
+                    _ => Some(GithubLink::PullRequest(reference)),
+                }
+            },
+        }
+    }
+
+    pub fn owner(&self) -> &str {
+        match self {
+            GithubLink::Issue(reference) | GithubLink::PullRequest(reference) => &reference.owner,
+            GithubLink::Commit { owner, .. } => owner,
+        }
+    }
+
+    pub fn repo(&self) -> &str {
+        match self {
+            GithubLink::Issue(reference) | GithubLink::PullRequest(reference) => &reference.repo,
+            GithubLink::Commit { repo, .. } => repo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod github_test {
+    use super::*;
+
+    #[test]
+    fn test_issue_reference_parse() {
+        assert_eq!(
+            Some(IssueReference { owner: "cloudflare".to_string(), repo: "wrangler-legacy".to_string(), number: 1 }),
+            IssueReference::parse("what's cloudflare/wrangler-legacy#1?")
+        );
+        assert_eq!(None, IssueReference::parse("cloudflare/wrangler-legacy#"));
+    }
+
+    #[test]
+    fn test_issue_reference_parse_all() {
+        assert_eq!(
+            vec![
+                IssueReference { owner: "reo0306".to_string(), repo: "rust-slack-bot".to_string(), number: 2 },
+                IssueReference { owner: "cloudflare".to_string(), repo: "workers-rs".to_string(), number: 30 },
+            ],
+            IssueReference::parse_all("see reo0306/rust-slack-bot#2 and cloudflare/workers-rs#30")
+        );
+    }
+
+    #[test]
+    fn test_search_item_reference() {
+        let item = SearchItem {
+            html_url: "https://github.com/reo0306/rust-slack-bot/pull/3".to_string(),
+            title: "test".to_string(),
+            number: 3,
+            repository_url: "https://api.github.com/repos/reo0306/rust-slack-bot".to_string(),
+        };
+
+        assert_eq!(
+            IssueReference { owner: "reo0306".to_string(), repo: "rust-slack-bot".to_string(), number: 3 },
+            item.reference()
+        );
+    }
+
+    #[test]
+    fn test_ci_status_from_check_runs() {
+        let run = |status: &str, conclusion: Option<&str>| CheckRun {
+            status: status.to_string(),
+            conclusion: conclusion.map(str::to_string),
+        };
+
+        assert_eq!(CiStatus::Unknown, CiStatus::from_check_runs(&[]));
+        assert_eq!(CiStatus::Success, CiStatus::from_check_runs(&[run("completed", Some("success")), run("completed", Some("skipped"))]));
+        assert_eq!(CiStatus::Pending, CiStatus::from_check_runs(&[run("completed", Some("success")), run("in_progress", None)]));
+        assert_eq!(CiStatus::Failure, CiStatus::from_check_runs(&[run("in_progress", None), run("completed", Some("failure"))]));
+    }
+
+    #[test]
+    fn test_github_link_parse() {
+        assert_eq!(
+            Some(GithubLink::Issue(IssueReference { owner: "cloudflare".to_string(), repo: "wrangler-legacy".to_string(), number: 1 })),
+            GithubLink::parse("https://github.com/cloudflare/wrangler-legacy/issues/1#issuecomment-123")
+        );
+        assert_eq!(
+            Some(GithubLink::PullRequest(IssueReference { owner: "reo0306".to_string(), repo: "rust-todo-di-app".to_string(), number: 1 })),
+            GithubLink::parse("https://github.com/reo0306/rust-todo-di-app/pull/1/files")
+        );
+        assert_eq!(
+            Some(GithubLink::Commit { owner: "reo0306".to_string(), repo: "rust-slack-bot".to_string(), sha: "7853d35".to_string() }),
+            GithubLink::parse("https://github.com/reo0306/rust-slack-bot/commit/7853d35")
+        );
+        assert_eq!(None, GithubLink::parse("https://github.com/reo0306/rust-slack-bot/blob/main/README.md"));
+    }
+
+    #[test]
+    fn test_card_state() {
+        let user = User {
+            html_url: "https://github.com/u".to_string(),
+            login: "u".to_string(),
+            avatar_url: "https://github.com/u.png".to_string(),
+        };
+        let issue = |state: State, state_reason: Option<StateReason>| Issue {
+            html_url: "https://github.com/o/r/issues/1".to_string(),
+            title: "t".to_string(),
+            body: None,
+            state,
+            state_reason,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 1,
+            user: user.clone(),
+            pull_request: None,
+        };
+
+        assert_eq!(CardState::Opened, issue(State::Open, None).card_state());
+        assert_eq!(CardState::Reopened, issue(State::Open, Some(StateReason::Reopened)).card_state());
+        assert_eq!(CardState::ClosedCompleted, issue(State::Closed, Some(StateReason::Completed)).card_state());
+        assert_eq!(CardState::ClosedNotPlanned, issue(State::Closed, Some(StateReason::NotPlanned)).card_state());
+
+        let pull_request = |state: State, draft: bool, merged: bool| PullRequest {
+            html_url: "https://github.com/o/r/pull/1".to_string(),
+            title: "t".to_string(),
+            body: None,
+            state,
+            draft,
+            labels: vec![],
+            created_at: "2024-07-07T20:09:31Z".to_string(),
+            number: 1,
+            user: user.clone(),
+            merged,
+            merged_by: None,
+            base: None,
+        };
+
+        assert_eq!(CardState::Opened, pull_request(State::Open, false, false).card_state());
+        assert_eq!(CardState::Draft, pull_request(State::Open, true, false).card_state());
+        assert_eq!(CardState::Merged, pull_request(State::Closed, false, true).card_state());
+        assert_eq!(CardState::Closed, pull_request(State::Closed, false, false).card_state());
+    }
+
+    #[test]
+    fn test_label_emoji() {
+        let label = |color: &str| Label { name: "label".to_string(), color: color.to_string() };
+
+        assert_eq!(":red_circle:", label("d73a4a").emoji());
+        assert_eq!(":large_blue_circle:", label("0075ca").emoji());
+        assert_eq!(":large_green_circle:", label("0e8a16").emoji());
+        assert_eq!(":large_yellow_circle:", label("fbca04").emoji());
+        assert_eq!(":large_purple_circle:", label("7057ff").emoji());
+        assert_eq!(":large_orange_circle:", label("ff9500").emoji());
+        assert_eq!(":large_brown_circle:", label("7a4100").emoji());
+        assert_eq!(":black_circle:", label("333333").emoji());
+        assert_eq!(":white_circle:", label("ededed").emoji());
+        assert_eq!(":white_circle:", label("").emoji());
+    }
 }
@@ -0,0 +1,378 @@
+use std::sync::OnceLock;
+use regex::Regex;
+
+/// Ends the label of links to GitHub users that the bot renders itself, by `user_link`. Only those
+/// links are turned into Slack mentions, so `escape` and `link` drop it from everything else.
+pub const USER_LINK_MARKER: char = '\u{2063}';
+
+/// Escapes untrusted text (titles, bodies, logins) so Slack renders it literally: no links,
+/// user or channel mentions, and no `@channel`/`@here` broadcasts.
+pub fn escape(text: &str) -> String {
+    let escaped = text
+        .replace(USER_LINK_MARKER, "")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+
+    // A word joiner after the `@` keeps the text readable while Slack no longer sees a keyword.
+    // Keywords right after a word, as in `me@everyone.example`, are part of an address and left alone.
+    static KEYWORD: OnceLock<Regex> = OnceLock::new();
+    let keyword = KEYWORD.get_or_init(|| Regex::new(r"(?i)(?P<before>^|[^\w.+-])@(?P<keyword>channel|here|everyone)\b").unwrap());
+
+    keyword.replace_all(&escaped, "$before@\u{2060}$keyword").to_string()
+}
+
+/// `<url|text>` with both parts escaped; a `|` in the URL would otherwise end it early.
+pub fn link(url: &str, text: &str) -> String {
+    let url = url
+        .replace(USER_LINK_MARKER, "")
+        .replace('&', "&amp;")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+        .replace('|', "%7C");
+
+    format!("<{}|{}>", url, escape(text))
+}
+
+/// A link to the GitHub user `login`, which `Message::mention_users` may replace with a mention
+/// of their Slack account. Meant for authors, assignees and actors, never for links out of a body.
+pub fn user_link(url: &str, login: &str) -> String {
+    let link = link(url, login);
+
+    format!("{}{}>", &link[..link.len() - 1], USER_LINK_MARKER)
+}
+
+/// Converts a GitHub Markdown body to mrkdwn. Everything that isn't Markdown syntax is escaped,
+/// HTML comments and tags are dropped and table separators are left out.
+pub fn from_markdown(markdown: &str) -> String {
+    let Syntax { comments, heading, task, bullet, ordered, quote, rule, table_separator, blank_lines, .. } = syntax();
+
+    let markdown = comments.replace_all(markdown, "");
+    let mut lines: Vec<String> = vec![];
+    let mut in_code = false;
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            in_code = !in_code;
+            lines.push("```".to_string());
+            continue;
+        }
+
+        if in_code {
+            lines.push(escape(line));
+            continue;
+        }
+
+        let converted = if let Some(caps) = heading.captures(line) {
+            format!("*{}*", inline(&caps["text"]))
+        } else if let Some(caps) = task.captures(line) {
+            let checkbox = if &caps["checked"] == " " { ":white_square:" } else { ":ballot_box_with_check:" };
+            format!("{}{} {}", &caps["indent"], checkbox, inline(&caps["text"]))
+        } else if rule.is_match(line) {
+            "───".to_string()
+        } else if let Some(caps) = bullet.captures(line) {
+            format!("{}• {}", &caps["indent"], inline(&caps["text"]))
+        } else if let Some(caps) = ordered.captures(line) {
+            format!("{}{}. {}", &caps["indent"], &caps["number"], inline(&caps["text"]))
+        } else if let Some(caps) = quote.captures(line) {
+            format!(">{}", inline(&caps["text"]))
+        } else if table_separator.is_match(line) && line.contains('|') {
+            continue;
+        } else if line.trim_start().starts_with('|') {
+            line.trim()
+                .trim_matches('|')
+                .split('|')
+                .map(|cell| inline(cell.trim()))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        } else {
+            inline(line.trim_end())
+        };
+
+        lines.push(converted);
+    }
+
+    if in_code {
+        lines.push("```".to_string());
+    }
+
+    blank_lines.replace_all(lines.join("\n").trim(), "\n\n").to_string()
+}
+
+/// Shortens converted mrkdwn to at most `max` characters, cutting at a line or word boundary
+/// and never inside a link, entity or code block, and appends a link to the full text.
+pub fn truncate(text: &str, max: usize, url: &str) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+
+    let read_more = format!("\n… {}", link(url, "Read more"));
+    let budget = max.saturating_sub(read_more.chars().count() + "\n```".len());
+    let cut = cut(text, budget);
+
+    let mut truncated = cut.trim_end().to_string();
+
+    if truncated.matches("```").count() % 2 == 1 {
+        truncated.push_str("\n```");
+    }
+
+    truncated + &read_more
+}
+
+/// Splits mrkdwn into chunks of at most `max` characters at line boundaries, so a long text can be
+/// spread over several blocks. Code blocks that are cut in two are closed and reopened.
+pub fn split(text: &str, max: usize) -> Vec<String> {
+    let budget = max.saturating_sub("```\n".len() + "\n```".len()).max(1);
+    let mut chunks: Vec<String> = vec![];
+    let mut chunk = String::new();
+    let mut length = 0;
+    let mut in_code = false;
+
+    for line in text.lines() {
+        let fence = line.trim_start().starts_with("```");
+        // A closing fence takes the room otherwise kept for closing the block on a split.
+        let limit = if fence && in_code { budget + "\n```".len() } else { budget };
+        let mut rest = line;
+
+        loop {
+            let piece = if rest.chars().count() > budget { cut(rest, budget) } else { rest };
+            let piece_length = piece.chars().count();
+
+            if length > 0 && length + 1 + piece_length > limit {
+                if in_code {
+                    chunk.push_str("\n```");
+                }
+
+                chunks.push(chunk);
+                chunk = if in_code { "```".to_string() } else { String::new() };
+                length = chunk.len();
+            }
+
+            if length > 0 {
+                chunk.push('\n');
+                length += 1;
+            }
+
+            chunk.push_str(piece);
+            length += piece_length;
+            rest = rest[piece.len()..].trim_start();
+
+            if rest.is_empty() {
+                break;
+            }
+        }
+
+        if fence {
+            in_code = !in_code;
+        }
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+/// The longest prefix of `text` within `max` characters, ending at a line or word boundary when
+/// that keeps at least half of it, and never inside a link or an entity.
+fn cut(text: &str, max: usize) -> &str {
+    let end = text.char_indices().nth(max).map(|(i, _)| i).unwrap_or(text.len());
+    let mut cut = &text[..end];
+
+    if let Some(i) = cut.rfind('\n').filter(|i| *i >= end / 2) {
+        cut = &cut[..i];
+    } else if let Some(i) = cut.rfind(' ').filter(|i| *i >= end / 2) {
+        cut = &cut[..i];
+    }
+
+    for (open, close) in [('<', '>'), ('&', ';')] {
+        if let Some(i) = cut.rfind(open).filter(|i| *i > 0 && cut.rfind(close).map_or(true, |j| j < *i)) {
+            cut = &cut[..i];
+        }
+    }
+
+    if cut.is_empty() { &text[..end] } else { cut }
+}
+
+/// The Markdown syntax `from_markdown` understands, compiled once rather than for every line.
+struct Syntax {
+    comments: Regex,
+    heading: Regex,
+    task: Regex,
+    bullet: Regex,
+    ordered: Regex,
+    quote: Regex,
+    rule: Regex,
+    table_separator: Regex,
+    blank_lines: Regex,
+    inline: Regex,
+}
+
+fn syntax() -> &'static Syntax {
+    static SYNTAX: OnceLock<Syntax> = OnceLock::new();
+
+    SYNTAX.get_or_init(|| Syntax {
+        comments: Regex::new(r"(?s)<!--.*?(-->|$)").unwrap(),
+        heading: Regex::new(r"^\s{0,3}#{1,6}\s+(?P<text>.*?)\s*#*\s*$").unwrap(),
+        task: Regex::new(r"^(?P<indent>\s*)[-*+]\s+\[(?P<checked>[ xX])\]\s+(?P<text>.*)$").unwrap(),
+        bullet: Regex::new(r"^(?P<indent>\s*)[-*+]\s+(?P<text>.*)$").unwrap(),
+        ordered: Regex::new(r"^(?P<indent>\s*)(?P<number>\d{1,9})[.)]\s+(?P<text>.*)$").unwrap(),
+        quote: Regex::new(r"^\s{0,3}>\s?(?P<text>.*)$").unwrap(),
+        rule: Regex::new(r"^\s{0,3}([-*_]\s*){3,}$").unwrap(),
+        table_separator: Regex::new(r"^\s*\|?(\s*:?-+:?\s*\|)+\s*(:?-+:?)?\s*$").unwrap(),
+        blank_lines: Regex::new(r"\n{3,}").unwrap(),
+        inline: Regex::new(concat!(
+            r"`(?P<code>[^`]+)`",
+            r"|!\[(?P<alt>[^\]]*)\]\((?P<image>[^)\s]+)[^)]*\)",
+            r"|\[(?P<label>[^\]]+)\]\((?P<href>[^)\s]+)[^)]*\)",
+            r"|<(?P<auto>https?://[^>\s|]+)>",
+            r#"|<img\s[^>]*?src="(?P<src>[^"]+)"[^>]*>"#,
+            r"|</?[a-zA-Z][a-zA-Z0-9-]*(\s[^<>]*)?/?>",
+            r"|\*\*(?P<bold>[^*]+?)\*\*|__(?P<underscore_bold>[^_]+?)__",
+            r"|~~(?P<strike>[^~]+?)~~",
+            r"|\*(?P<em>[^*\s][^*]*?)\*|\b_(?P<underscore_em>[^_\s][^_]*?)_\b",
+        )).unwrap(),
+    })
+}
+
+fn inline(text: &str) -> String {
+    let re = &syntax().inline;
+
+    let mut converted = String::new();
+    let mut last = 0;
+
+    for caps in re.captures_iter(text) {
+        let all = caps.get(0).unwrap();
+        converted.push_str(&escape(&text[last..all.start()]));
+        last = all.end();
+
+        let replacement = if let Some(code) = caps.name("code") {
+            format!("`{}`", escape(code.as_str()))
+        } else if let Some(image) = caps.name("image") {
+            let alt = caps.name("alt").map_or("", |alt| alt.as_str());
+            link(image.as_str(), if alt.is_empty() { "image" } else { alt })
+        } else if let Some(href) = caps.name("href") {
+            link(href.as_str(), &caps["label"])
+        } else if let Some(auto) = caps.name("auto") {
+            link(auto.as_str(), auto.as_str())
+        } else if let Some(src) = caps.name("src") {
+            link(src.as_str(), "image")
+        } else if let Some(bold) = caps.name("bold").or(caps.name("underscore_bold")) {
+            format!("*{}*", inline(bold.as_str()))
+        } else if let Some(strike) = caps.name("strike") {
+            format!("~{}~", inline(strike.as_str()))
+        } else if let Some(em) = caps.name("em").or(caps.name("underscore_em")) {
+            format!("_{}_", inline(em.as_str()))
+        } else {
+            String::new()
+        };
+
+        converted.push_str(&replacement);
+    }
+
+    converted.push_str(&escape(&text[last..]));
+
+    converted
+}
+
+#[cfg(test)]
+mod mrkdwn_test {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("Fix &lt;!channel&gt; &amp; &lt;@U123&gt;", escape("Fix <!channel> & <@U123>"));
+        assert_eq!("&lt;https://evil.example|click me&gt;", escape("<https://evil.example|click me>"));
+        assert_eq!("cc @\u{2060}here and @\u{2060}Channel, not @channels", escape("cc @here and @Channel, not @channels"));
+        assert_eq!("me@everyone.example and first.last+tag@here.example", escape("me@everyone.example and first.last+tag@here.example"));
+        assert_eq!("@\u{2060}here, (@\u{2060}everyone) &gt;@\u{2060}channel", escape("@here, (@everyone) >@channel"));
+        assert_eq!("plain *bold* text", escape("plain *bold* text"));
+    }
+
+    #[test]
+    fn test_link() {
+        assert_eq!(
+            "<https://github.com/o/r/issues/1|&lt;!here&gt; title>",
+            link("https://github.com/o/r/issues/1", "<!here> title")
+        );
+        assert_eq!(
+            "<https://example.com/?a=1&amp;b=%7C%3E|x>",
+            link("https://example.com/?a=1&b=|>", "x")
+        );
+    }
+
+    #[test]
+    fn test_user_link() {
+        assert_eq!("<https://github.com/octocat|octocat\u{2063}>", user_link("https://github.com/octocat", "octocat"));
+        assert_eq!("<https://github.com/octocat|octocat>", link("https://github.com/octocat\u{2063}", "octocat\u{2063}"));
+        assert_eq!("[octocat](https://github.com/octocat)", escape("[octocat\u{2063}](https://github.com/octocat)"));
+    }
+
+    #[test]
+    fn test_from_markdown_inline() {
+        assert_eq!(
+            "*bold* and *bold*, _em_ and _em_, ~gone~ and `a &lt; b`",
+            from_markdown("**bold** and __bold__, *em* and _em_, ~~gone~~ and `a < b`")
+        );
+        assert_eq!(
+            "See <https://example.com/a?b=1&amp;c=2|the docs> or <https://example.com|https://example.com>",
+            from_markdown("See [the docs](https://example.com/a?b=1&c=2 \"title\") or <https://example.com>")
+        );
+        assert_eq!(
+            "<https://example.com/a.png|screenshot> <https://example.com/b.png|image>",
+            from_markdown("![screenshot](https://example.com/a.png) <img width=\"200\" src=\"https://example.com/b.png\">")
+        );
+        assert_eq!("snake_case_name stays", from_markdown("snake_case_name stays"));
+    }
+
+    #[test]
+    fn test_from_markdown_blocks() {
+        let markdown = "<!-- Describe your change -->\n## Summary\n\nFixes **stuff**.\n\n- [x] Tests\n- [ ] Docs\n  * nested\n1. first\n\n> quoted <b>text</b>\n\n| a | b |\n|---|:-:|\n| 1 | 2 |\n\n---\n```rust\nlet x = a < b && **c**;\n```\n<!--\nhidden\n-->";
+
+        assert_eq!(
+            "*Summary*\n\nFixes *stuff*.\n\n:ballot_box_with_check: Tests\n:white_square: Docs\n  • nested\n1. first\n\n>quoted text\n\na | b\n1 | 2\n\n───\n```\nlet x = a &lt; b &amp;&amp; **c**;\n```",
+            from_markdown(markdown)
+        );
+    }
+
+    #[test]
+    fn test_from_markdown_unclosed_code_block() {
+        assert_eq!("```\n&lt;!channel&gt;\n```", from_markdown("```\n<!channel>"));
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(vec!["short".to_string()], split("short", 100));
+
+        let text = format!("{}\n{}\n{}", "a".repeat(10), "b".repeat(10), "c".repeat(10));
+        assert_eq!(vec![format!("{}\n{}", "a".repeat(10), "b".repeat(10)), "c".repeat(10)], split(&text, 30));
+
+        let text = "```\none\ntwo\nthree\n```\nafter";
+        assert_eq!(vec!["```\none\ntwo\n```", "```\nthree\n```", "after"], split(text, 20));
+
+        let text = format!("{} <https://example.com|link> {}", "word ".repeat(4).trim(), "x".repeat(30));
+        assert_eq!(vec!["word word word word", "<https://example.com|link>", "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"], split(&text, 38));
+
+        assert!(split(&"long line ".repeat(1000), 3000).iter().all(|chunk| chunk.chars().count() <= 3000));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let url = "https://github.com/o/r/issues/1";
+
+        assert_eq!("short", truncate("short", 100, url));
+        assert_eq!(
+            "first line\n… <https://github.com/o/r/issues/1|Read more>",
+            truncate("first line\nsecond line is long enough to be cut off by the limit of this test", 70, url)
+        );
+        assert_eq!(
+            "see\n… <https://github.com/o/r/issues/1|Read more>",
+            truncate("see <https://example.com/a/very/long/path|a link> &amp; more text", 60, url)
+        );
+        assert_eq!(
+            "```\ncode\nmore code\n```\n… <https://github.com/o/r/issues/1|Read more>",
+            truncate("```\ncode\nmore code\nand even more code that does not fit in the message\n```", 70, url)
+        );
+    }
+}
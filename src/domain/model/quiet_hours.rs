@@ -0,0 +1,100 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Serialize, Deserialize};
+
+/// A daily window in which a channel only receives urgent events; the rest waits for a catch-up.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// IANA name like `Europe/Berlin`, so the window follows daylight saving time.
+    pub timezone: String,
+}
+
+impl QuietHours {
+    /// Parses `22:00-07:00` and a timezone name; windows that wrap past midnight are fine, empty ones are not.
+    /// The error says what to fix.
+    pub fn parse(window: &str, timezone: &str) -> Result<QuietHours, String> {
+        let time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").ok();
+        let (start, end) = match window.split_once('-').map(|(start, end)| (time(start), time(end))) {
+            Some((Some(start), Some(end))) => (start, end),
+            _ => return Err(format!("`{}` is not a window like `22:00-07:00`", window)),
+        };
+        let timezone: Tz = timezone
+            .parse()
+            .map_err(|_| format!("`{}` is not a timezone; use a name like `Europe/Berlin` or `America/New_York`", timezone))?;
+
+        if start == end {
+            return Err("Quiet hours need to end at a different time than they start".to_string());
+        }
+
+        Ok(QuietHours { start, end, timezone: timezone.name().to_string() })
+    }
+
+    pub fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        let timezone: Tz = match self.timezone.parse() {
+            Ok(timezone) => timezone,
+            Err(_) => return false,
+        };
+        let time = now.with_timezone(&timezone).time();
+
+        match self.start < self.end {
+            true => self.start <= time && time < self.end,
+            false => self.start <= time || time < self.end,
+        }
+    }
+}
+
+/// Events carrying one of `urgent_labels`, like `security`, break through quiet hours.
+pub fn is_urgent(labels: &[String], urgent_labels: &[String]) -> bool {
+    labels.iter().any(|label| urgent_labels.iter().any(|urgent| urgent.eq_ignore_ascii_case(label)))
+}
+
+#[cfg(test)]
+mod quiet_hours_test {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse() {
+        let quiet_hours = QuietHours::parse("22:00-07:30", "Europe/Berlin").unwrap();
+
+        assert_eq!(NaiveTime::from_hms_opt(22, 0, 0).unwrap(), quiet_hours.start);
+        assert_eq!(NaiveTime::from_hms_opt(7, 30, 0).unwrap(), quiet_hours.end);
+        assert_eq!("Europe/Berlin", quiet_hours.timezone);
+        assert!(QuietHours::parse("22:00-22:00", "UTC").is_err());
+        assert_eq!(Err("`22:00` is not a window like `22:00-07:00`".to_string()), QuietHours::parse("22:00", "UTC"));
+        assert_eq!(
+            Err("`Mars/Olympus` is not a timezone; use a name like `Europe/Berlin` or `America/New_York`".to_string()),
+            QuietHours::parse("22:00-07:00", "Mars/Olympus")
+        );
+    }
+
+    #[test]
+    fn test_is_quiet() {
+        let overnight = QuietHours::parse("22:00-07:00", "Europe/Berlin").unwrap();
+
+        assert!(overnight.is_quiet(at("2024-07-08T01:00:00Z")));
+        assert!(overnight.is_quiet(at("2024-07-07T20:00:00Z")));
+        assert!(!overnight.is_quiet(at("2024-07-08T05:00:00Z")));
+        // 05:30 UTC is 07:30 in summer but still 06:30 in winter.
+        assert!(!overnight.is_quiet(at("2024-07-08T05:30:00Z")));
+        assert!(overnight.is_quiet(at("2024-01-08T05:30:00Z")));
+
+        let lunch = QuietHours::parse("12:00-13:00", "UTC").unwrap();
+
+        assert!(lunch.is_quiet(at("2024-07-08T12:30:00Z")));
+        assert!(!lunch.is_quiet(at("2024-07-08T13:00:00Z")));
+    }
+
+    #[test]
+    fn test_is_urgent() {
+        let urgent = vec!["security".to_string(), "incident".to_string()];
+
+        assert!(is_urgent(&["bug".to_string(), "Security".to_string()], &urgent));
+        assert!(!is_urgent(&["bug".to_string()], &urgent));
+    }
+}
@@ -0,0 +1,233 @@
+use serde::{Serialize, Deserialize};
+
+use crate::domain::model::{
+    digest::Frequency,
+    slack::MessageSink,
+};
+
+/// What happens to events of bot accounts, set through `BOT_EVENTS`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BotPolicy {
+    Deliver,
+    Suppress,
+    /// Collected and posted as one message by the scheduled handler.
+    Rollup,
+}
+
+impl BotPolicy {
+    pub fn parse(value: &str) -> BotPolicy {
+        match value {
+            "suppress" => BotPolicy::Suppress,
+            "rollup" => BotPolicy::Rollup,
+            _ => BotPolicy::Deliver,
+        }
+    }
+}
+
+/// GitHub Apps post as `name[bot]`; other automation accounts are listed in `accounts`.
+pub fn is_bot(login: &str, accounts: &[String]) -> bool {
+    login.ends_with("[bot]") || accounts.iter().any(|account| account.eq_ignore_ascii_case(login))
+}
+
+/// Held back events, posted to their sink as one message by the scheduled handler.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Queue {
+    /// Events of bot accounts.
+    Rollup,
+    /// Events that arrived during the quiet hours of a channel.
+    CatchUp,
+    /// Events of subscriptions in digest mode.
+    Digest(Frequency),
+}
+
+impl Queue {
+    pub fn prefix(&self) -> &str {
+        match self {
+            Queue::Rollup => "rollup",
+            Queue::CatchUp => "catch_up",
+            Queue::Digest(Frequency::Daily) => "digest_daily",
+            Queue::Digest(Frequency::Weekly) => "digest_weekly",
+        }
+    }
+}
+
+/// One event waiting in a queue. Each is stored under a key of its own, so webhooks arriving at
+/// the same time cannot overwrite one another's.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct QueuedEntry {
+    pub sink: MessageSink,
+    pub entry: RollupEntry,
+}
+
+/// Events waiting in a queue for one sink, with the keys they are stored under.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Rollup {
+    pub sink: MessageSink,
+    pub entries: Vec<RollupEntry>,
+    pub keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RollupEntry {
+    pub repository: String,
+    /// Releases have no number.
+    pub number: Option<u32>,
+    pub title: String,
+    pub html_url: String,
+    pub author: String,
+    /// Who triggered the event, when someone other than the author.
+    #[serde(default)]
+    pub actor: Option<String>,
+    pub kind: ItemKind,
+    /// Past tense, like `opened` or `merged`.
+    pub action: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Issue,
+    PullRequest,
+    Release,
+}
+
+impl RollupEntry {
+    /// Webhook actions as they read in a sentence; merged pull requests arrive as `closed`.
+    pub fn action_text(action: &str, merged: bool) -> String {
+        match (action, merged) {
+            ("closed", true) => "merged".to_string(),
+            ("synchronize", _) => "updated".to_string(),
+            ("ready_for_review", _) => "marked ready".to_string(),
+            (action, _) => action.replace('_', " "),
+        }
+    }
+}
+
+impl Rollup {
+    pub fn new(sink: MessageSink) -> Rollup {
+        Rollup { sink, entries: vec![], keys: vec![] }
+    }
+
+    /// Gathers queued entries by sink, keeping the order they were listed in.
+    pub fn group(queued: Vec<(String, QueuedEntry)>) -> Vec<Rollup> {
+        let mut rollups: Vec<Rollup> = vec![];
+
+        for (key, QueuedEntry { sink, entry }) in queued {
+            let rollup = match rollups.iter().position(|rollup| rollup.sink == sink) {
+                Some(index) => &mut rollups[index],
+                None => {
+                    rollups.push(Rollup::new(sink));
+                    rollups.last_mut().unwrap()
+                },
+            };
+
+            rollup.entries.push(entry);
+            rollup.keys.push(key);
+        }
+
+        rollups
+    }
+
+    /// Counts per action, pull requests first: `7 pull requests opened, 3 merged; 1 issue opened`.
+    pub fn summary(&self) -> String {
+        [(ItemKind::PullRequest, "pull request"), (ItemKind::Issue, "issue"), (ItemKind::Release, "release")]
+            .into_iter()
+            .filter_map(|(kind, noun)| {
+                let mut counts: Vec<(&str, usize)> = vec![];
+
+                for entry in self.entries.iter().filter(|entry| entry.kind == kind) {
+                    match counts.iter_mut().find(|(action, _)| *action == entry.action) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((&entry.action, 1)),
+                    }
+                }
+
+                let ((first_action, first_count), rest) = counts.split_first()?;
+                let plural = if *first_count == 1 { "" } else { "s" };
+                let mut text = format!("{} {}{} {}", first_count, noun, plural, first_action);
+
+                for (action, count) in rest {
+                    text.push_str(&format!(", {} {}", count, action));
+                }
+
+                Some(text)
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+#[cfg(test)]
+mod rollup_test {
+    use super::*;
+
+    fn entry(number: Option<u32>, kind: ItemKind, action: &str) -> RollupEntry {
+        RollupEntry {
+            repository: "org/api".to_string(),
+            number,
+            title: "Bump serde".to_string(),
+            html_url: format!("https://github.com/org/api/pull/{}", number.unwrap_or_default()),
+            author: "dependabot[bot]".to_string(),
+            actor: None,
+            kind,
+            action: action.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_is_bot() {
+        let accounts = vec!["ci-runner".to_string()];
+
+        assert!(is_bot("dependabot[bot]", &accounts));
+        assert!(is_bot("CI-Runner", &accounts));
+        assert!(!is_bot("octocat", &accounts));
+    }
+
+    #[test]
+    fn test_bot_policy() {
+        assert_eq!(BotPolicy::Rollup, BotPolicy::parse("rollup"));
+        assert_eq!(BotPolicy::Suppress, BotPolicy::parse("suppress"));
+        assert_eq!(BotPolicy::Deliver, BotPolicy::parse(""));
+    }
+
+    #[test]
+    fn test_action_text() {
+        assert_eq!("merged", RollupEntry::action_text("closed", true));
+        assert_eq!("closed", RollupEntry::action_text("closed", false));
+        assert_eq!("review requested", RollupEntry::action_text("review_requested", false));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut rollup = Rollup::new(MessageSink::IncomingWebhook("https://hooks.slack.com/x".to_string()));
+
+        assert_eq!("", rollup.summary());
+
+        rollup.entries = vec![
+            entry(Some(1), ItemKind::PullRequest, "opened"),
+            entry(Some(2), ItemKind::PullRequest, "opened"),
+            entry(Some(1), ItemKind::PullRequest, "merged"),
+            entry(Some(3), ItemKind::Issue, "opened"),
+            entry(None, ItemKind::Release, "published"),
+        ];
+
+        assert_eq!("2 pull requests opened, 1 merged; 1 issue opened; 1 release published", rollup.summary());
+    }
+
+    #[test]
+    fn test_group() {
+        let channel = |channel: &str| MessageSink::Channel { team_id: "T1".to_string(), channel: channel.to_string() };
+        let queued = |key: &str, sink: MessageSink, number| (key.to_string(), QueuedEntry { sink, entry: entry(Some(number), ItemKind::Issue, "opened") });
+
+        let rollups = Rollup::group(vec![
+            queued("rollup:T1:C1:1", channel("C1"), 1),
+            queued("rollup:T1:C2:2", channel("C2"), 2),
+            queued("rollup:T1:C1:3", channel("C1"), 3),
+        ]);
+
+        assert_eq!(2, rollups.len());
+        assert_eq!(channel("C1"), rollups[0].sink);
+        assert_eq!(vec![Some(1), Some(3)], rollups[0].entries.iter().map(|entry| entry.number).collect::<Vec<_>>());
+        assert_eq!(vec!["rollup:T1:C1:1".to_string(), "rollup:T1:C1:3".to_string()], rollups[0].keys);
+    }
+}
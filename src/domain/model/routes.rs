@@ -1,10 +1,48 @@
+use regex::Regex;
 use serde::Deserialize;
 
-use crate::domain::model::github::{Issue, PullRequest};
+use crate::domain::model::{
+    filter::FilterEvent,
+    rollup::{is_bot, ItemKind, RollupEntry},
+    github::{CardState, Issue, IssueReference, PayloadRepository, PullRequest, User},
+    slack::{ReactionChange, SlackEvent},
+};
 
 #[derive(Deserialize)]
 pub struct SlashCommandRequest {
-    pub text: String
+    pub text: String,
+    pub channel_id: String,
+    #[serde(default)]
+    pub team_id: String,
+    #[serde(default)]
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct InteractionRequest {
+    pub payload: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SlackEventRequest {
+    UrlVerification {
+        challenge: String,
+    },
+    EventCallback {
+        team_id: String,
+        #[serde(default)]
+        authorizations: Vec<Authorization>,
+        event: SlackEvent,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize)]
+pub struct Authorization {
+    pub user_id: String,
+    pub is_bot: bool,
 }
 
 #[derive(Deserialize)]
@@ -12,7 +50,83 @@ pub struct GithubWebhookRequest {
     pub action: String,
     pub issue: Option<Issue>,
     pub pull_request: Option<PullRequest>,
+    pub review: Option<Review>,
+    pub comment: Option<Comment>,
+    pub requested_reviewer: Option<User>,
+    pub assignee: Option<User>,
+    pub sender: Option<User>,
+    #[serde(default)]
+    pub release: Option<Release>,
     pub repository: ReqRepo,
+    /// `X-GitHub-Event`, which the payload itself does not carry. `webhook` fills it in.
+    #[serde(skip)]
+    pub event_name: Option<String>,
+}
+
+/// `security_advisory` and `deployment_status` events. Published advisories and failed deployments
+/// are urgent: they reach every channel following the repository right away, quiet or not.
+#[derive(Deserialize)]
+pub struct AlertWebhookRequest {
+    pub action: String,
+    /// Advisories are published to the whole of GitHub rather than to a repository.
+    pub repository: Option<ReqRepo>,
+    pub security_advisory: Option<SecurityAdvisory>,
+    pub deployment_status: Option<DeploymentStatus>,
+    pub deployment: Option<Deployment>,
+}
+
+#[derive(Deserialize)]
+pub struct SecurityAdvisory {
+    pub ghsa_id: String,
+    pub summary: String,
+    pub severity: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeploymentStatus {
+    pub state: String,
+    pub environment: String,
+    pub target_url: Option<String>,
+    pub log_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Deployment {
+    #[serde(rename = "ref")]
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub html_url: String,
+    pub author: User,
+}
+
+#[derive(Deserialize)]
+pub struct Review {
+    pub state: String,
+}
+
+#[derive(Deserialize)]
+pub struct Comment {
+    pub html_url: String,
+    pub body: String,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum NotificationReason {
+    ReviewRequested,
+    Assigned,
+    Mentioned,
+}
+
+/// A GitHub user to tell about an event directly rather than through the channel.
+#[derive(PartialEq, Debug)]
+pub struct DirectNotification {
+    pub login: String,
+    pub reason: NotificationReason,
 }
 
 #[derive(Deserialize)]
@@ -34,4 +148,238 @@ impl GithubWebhookRequest {
             _ => "",
         }
     }
+
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.repository.owner.login, self.repository.name)
+    }
+
+    /// The event type GitHub sent the payload as. Without the header it is told apart by what the
+    /// payload carries: comments on a pull request's diff come with the pull request, others with the issue.
+    pub fn event(&self) -> &str {
+        if let Some(event_name) = &self.event_name {
+            return event_name;
+        }
+
+        if self.release.is_some() {
+            return "release";
+        }
+
+        match (&self.review, &self.comment, &self.pull_request) {
+            (Some(_), _, _) => "pull_request_review",
+            (None, Some(_), Some(_)) => "pull_request_review_comment",
+            (None, Some(_), None) => "issue_comment",
+            (None, None, Some(_)) => "pull_request",
+            (None, None, None) => "issues",
+        }
+    }
+
+    /// What subscription filters look at. Changed paths are left for the caller to fetch.
+    pub fn filter_event(&self) -> FilterEvent {
+        let (author, labels) = match (&self.issue, &self.pull_request, &self.release) {
+            (Some(issue), None, _) => (Some(&issue.user), issue.labels()),
+            (None, Some(pr), _) => (Some(&pr.user), pr.labels()),
+            (None, None, Some(release)) => (Some(&release.author), &[][..]),
+            _ => (None, &[][..]),
+        };
+
+        FilterEvent {
+            pull_request: self.is_pull_request(),
+            action: self.action.to_string(),
+            author: author.map(|user| user.login.to_string()),
+            labels: labels.iter().map(|label| label.name.to_string()).collect(),
+            base: self.pull_request.as_ref().and_then(|pr| pr.base.as_ref()).map(|base| base.name.to_string()),
+            draft: self.pull_request.as_ref().map(|pr| pr.draft),
+            paths: vec![],
+        }
+    }
+
+    /// Events on items a bot opened, except people commenting on or reviewing them.
+    pub fn is_bot_activity(&self, accounts: &[String]) -> bool {
+        let author = match (&self.issue, &self.pull_request) {
+            (Some(issue), None) => &issue.user,
+            (None, Some(pr)) => &pr.user,
+            _ => return false,
+        };
+        let by_person = (self.comment.is_some() || self.review.is_some())
+            && self.sender.as_ref().is_some_and(|sender| !is_bot(&sender.login, accounts));
+
+        is_bot(&author.login, accounts) && !by_person
+    }
+
+    pub fn rollup_entry(&self) -> Option<RollupEntry> {
+        let actor = self.sender.as_ref().map(|sender| sender.login.to_string());
+
+        if let Some(release) = &self.release {
+            return Some(RollupEntry {
+                repository: self.full_name(),
+                number: None,
+                title: release.name.as_deref().filter(|name| !name.is_empty()).unwrap_or(&release.tag_name).to_string(),
+                html_url: release.html_url.to_string(),
+                author: release.author.login.to_string(),
+                actor,
+                kind: ItemKind::Release,
+                action: self.action.to_string(),
+            });
+        }
+
+        let (item, kind, merged): (&dyn PayloadRepository, ItemKind, bool) = match (&self.issue, &self.pull_request) {
+            (Some(issue), None) => (issue, ItemKind::Issue, false),
+            (None, Some(pr)) => (pr, ItemKind::PullRequest, pr.merged),
+            _ => return None,
+        };
+        let action = match self.event() {
+            "pull_request_review" => "reviewed".to_string(),
+            "issue_comment" | "pull_request_review_comment" => "commented".to_string(),
+            _ => RollupEntry::action_text(&self.action, merged),
+        };
+
+        Some(RollupEntry {
+            repository: self.full_name(),
+            number: Some(item.number()),
+            title: item.title().to_string(),
+            html_url: item.html_url().to_string(),
+            author: item.user().login.to_string(),
+            actor,
+            kind,
+            action,
+        })
+    }
+
+    pub fn reference(&self) -> Option<IssueReference> {
+        let number = match (&self.issue, &self.pull_request) {
+            (Some(issue), None) => issue.number,
+            (None, Some(pr)) => pr.number,
+            _ => return None,
+        };
+
+        Some(IssueReference {
+            owner: self.repository.owner.login.to_string(),
+            repo: self.repository.name.to_string(),
+            number,
+        })
+    }
+
+    /// Pull requests carry no state reason, so a reopened one is only told apart by the action.
+    pub fn card_state<T: PayloadRepository>(&self, payload: &T) -> CardState {
+        match (self.action.as_str(), payload.card_state()) {
+            ("reopened", CardState::Opened) => CardState::Reopened,
+            (_, state) => state,
+        }
+    }
+
+    /// :eyes: while a review is pending, then the review outcome, and :tada: once merged.
+    pub fn reactions(&self) -> Option<ReactionChange> {
+        let pr = self.pull_request.as_ref()?;
+
+        let change = match (self.action.as_str(), self.review.as_ref().map(|review| review.state.as_str())) {
+            ("review_requested", _) => ReactionChange {
+                add: vec!["eyes"],
+                remove: vec![],
+            },
+            ("submitted", Some("approved")) => ReactionChange {
+                add: vec!["white_check_mark"],
+                remove: vec!["eyes", "x"],
+            },
+            ("submitted", Some("changes_requested")) => ReactionChange {
+                add: vec!["x"],
+                remove: vec!["eyes", "white_check_mark"],
+            },
+            ("closed", _) if pr.merged => ReactionChange {
+                add: vec!["tada"],
+                remove: vec!["eyes"],
+            },
+            _ => return None,
+        };
+
+        Some(change)
+    }
+
+    /// Reviewers, assignees and users mentioned in a new body or comment, except whoever caused the event.
+    pub fn direct_notifications(&self) -> Vec<DirectNotification> {
+        let notification = |user: &Option<User>, reason| user.as_ref().map(|user| DirectNotification {
+            login: user.login.to_string(),
+            reason,
+        });
+
+        let mut notifications: Vec<DirectNotification> = match self.action.as_str() {
+            "review_requested" => notification(&self.requested_reviewer, NotificationReason::ReviewRequested).into_iter().collect(),
+            "assigned" => notification(&self.assignee, NotificationReason::Assigned).into_iter().collect(),
+            "opened" | "created" => {
+                let text = match (&self.comment, &self.issue, &self.pull_request) {
+                    (Some(comment), _, _) => Some(comment.body.as_str()),
+                    (None, Some(issue), None) => issue.body.as_deref(),
+                    (None, None, Some(pr)) => pr.body.as_deref(),
+                    _ => None,
+                };
+
+                mentioned_logins(text.unwrap_or(""))
+                    .into_iter()
+                    .map(|login| DirectNotification {
+                        login,
+                        reason: NotificationReason::Mentioned,
+                    })
+                    .collect()
+            },
+            _ => vec![],
+        };
+
+        if let Some(sender) = &self.sender {
+            notifications.retain(|notification| !notification.login.eq_ignore_ascii_case(&sender.login));
+        }
+
+        notifications
+    }
+
+    /// Comments on a pull request's conversation arrive as `issue_comment` with the issue side of it.
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some() || self.issue.as_ref().is_some_and(|issue| issue.pull_request.is_some())
+    }
+
+    /// The issue or pull request itself was deleted, as opposed to one of its comments or reviews.
+    pub fn is_item_deleted(&self) -> bool {
+        self.action == "deleted" && self.comment.is_none() && self.review.is_none()
+    }
+
+    /// Events worth surfacing in the channel even when they are threaded under the original card.
+    pub fn is_important(&self) -> bool {
+        self.action == "closed"
+    }
+}
+
+impl AlertWebhookRequest {
+    pub fn full_name(&self) -> Option<String> {
+        self.repository.as_ref().map(|repository| format!("{}/{}", repository.owner.login, repository.name))
+    }
+
+    pub fn event(&self) -> &str {
+        match self.security_advisory {
+            Some(_) => "security_advisory",
+            None => "deployment_status",
+        }
+    }
+
+    pub fn is_urgent(&self) -> bool {
+        match (&self.security_advisory, &self.deployment_status) {
+            (Some(_), _) => self.action == "published",
+            (None, Some(status)) => matches!(status.state.as_str(), "failure" | "error"),
+            (None, None) => false,
+        }
+    }
+}
+
+/// `@login` mentions, leaving out email addresses and `@org/team` mentions. At most ten users are picked up.
+fn mentioned_logins(text: &str) -> Vec<String> {
+    let re = Regex::new(r"(?:^|[^\w@/`])@(?P<login>[A-Za-z0-9][A-Za-z0-9-]{0,38})(?P<team>/)?").unwrap();
+    let mut logins: Vec<String> = vec![];
+
+    for caps in re.captures_iter(text) {
+        let login = caps["login"].to_string();
+
+        if caps.name("team").is_none() && !logins.iter().any(|known| known.eq_ignore_ascii_case(&login)) {
+            logins.push(login);
+        }
+    }
+
+    logins.truncate(10);
+    logins
 }
@@ -0,0 +1,167 @@
+use serde::{Serialize, Deserialize};
+
+/// Webhook event types a route can be limited to.
+pub const ROUTABLE_EVENTS: [&str; 4] = ["issues", "pull_request", "pull_request_review", "issue_comment"];
+
+/// Which events of which repositories go to which channels, kept per workspace.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct RoutingTable {
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// Receives events no route matches.
+    #[serde(default)]
+    pub default_channel: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Route {
+    /// `owner/repo`, compared case-insensitively like GitHub does.
+    pub repository: String,
+    /// Limits the route to one event type; `None` matches all of them.
+    #[serde(default)]
+    pub event: Option<String>,
+    pub channels: Vec<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum RouteScope {
+    Default,
+    Repository {
+        repository: String,
+        event: Option<String>,
+    },
+}
+
+impl RoutingTable {
+    /// Channels for an event, falling back to the default channel and then to `fallback` when no route matches.
+    pub fn channels(&self, repository: &str, event: &str, fallback: Option<&str>) -> Vec<String> {
+        let mut channels = self.routed(repository, event);
+
+        if channels.is_empty() {
+            channels.extend(self.default_channel.as_deref().or(fallback).map(str::to_string));
+        }
+
+        channels
+    }
+
+    /// Channels of the routes matching an event, without any fallback.
+    pub fn routed(&self, repository: &str, event: &str) -> Vec<String> {
+        let mut channels: Vec<String> = vec![];
+
+        for route in self.routes.iter().filter(|route| route.matches(repository, event)) {
+            for channel in &route.channels {
+                if !channels.contains(channel) {
+                    channels.push(channel.to_string());
+                }
+            }
+        }
+
+        channels
+    }
+
+    pub fn add(&mut self, scope: &RouteScope, channel: &str) {
+        let (repository, event) = match scope {
+            RouteScope::Default => {
+                self.default_channel = Some(channel.to_string());
+                return;
+            },
+            RouteScope::Repository { repository, event } => (repository, event),
+        };
+
+        match self.routes.iter_mut().find(|route| route.is(repository, event.as_deref())) {
+            Some(route) if route.channels.iter().any(|c| c == channel) => {},
+            Some(route) => route.channels.push(channel.to_string()),
+            None => self.routes.push(Route {
+                repository: repository.to_string(),
+                event: event.clone(),
+                channels: vec![channel.to_string()],
+            }),
+        }
+    }
+
+    /// Returns whether `channel` was routed for `scope`. Routes left without channels are dropped.
+    pub fn remove(&mut self, scope: &RouteScope, channel: &str) -> bool {
+        let (repository, event) = match scope {
+            RouteScope::Default if self.default_channel.as_deref() == Some(channel) => {
+                self.default_channel = None;
+                return true;
+            },
+            RouteScope::Default => return false,
+            RouteScope::Repository { repository, event } => (repository, event),
+        };
+
+        let mut removed = false;
+
+        for route in self.routes.iter_mut().filter(|route| route.is(repository, event.as_deref())) {
+            let before = route.channels.len();
+            route.channels.retain(|c| c != channel);
+            removed |= route.channels.len() != before;
+        }
+
+        self.routes.retain(|route| !route.channels.is_empty());
+
+        removed
+    }
+}
+
+impl Route {
+    fn matches(&self, repository: &str, event: &str) -> bool {
+        self.repository.eq_ignore_ascii_case(repository) && self.event.as_deref().map_or(true, |e| e == event)
+    }
+
+    fn is(&self, repository: &str, event: Option<&str>) -> bool {
+        self.repository.eq_ignore_ascii_case(repository) && self.event.as_deref() == event
+    }
+}
+
+#[cfg(test)]
+mod routing_test {
+    use super::*;
+
+    fn scope(repository: &str, event: Option<&str>) -> RouteScope {
+        RouteScope::Repository {
+            repository: repository.to_string(),
+            event: event.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_channels() {
+        let mut table = RoutingTable::default();
+        table.add(&scope("org/api", None), "C_API");
+        table.add(&scope("org/api", Some("pull_request")), "C_REVIEWS");
+        table.add(&scope("org/web", None), "C_WEB");
+
+        assert_eq!(vec!["C_API"], table.channels("org/api", "issues", Some("C_ALL")));
+        assert_eq!(vec!["C_API", "C_REVIEWS"], table.channels("Org/API", "pull_request", Some("C_ALL")));
+        assert_eq!(vec!["C_WEB"], table.channels("org/web", "pull_request", Some("C_ALL")));
+        assert_eq!(vec!["C_ALL"], table.channels("org/other", "issues", Some("C_ALL")));
+        assert!(table.channels("org/other", "issues", None).is_empty());
+        assert!(table.routed("org/other", "issues").is_empty());
+
+        table.add(&RouteScope::Default, "C_DEFAULT");
+
+        assert_eq!(vec!["C_DEFAULT"], table.channels("org/other", "issues", Some("C_ALL")));
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut table = RoutingTable::default();
+        table.add(&scope("org/api", None), "C_API");
+        table.add(&scope("org/api", None), "C_API");
+        table.add(&scope("org/api", None), "C_MORE");
+
+        assert_eq!(vec!["C_API", "C_MORE"], table.routes[0].channels);
+
+        assert!(table.remove(&scope("org/api", None), "C_API"));
+        assert!(!table.remove(&scope("org/api", Some("issues")), "C_MORE"));
+        assert!(table.remove(&scope("org/api", None), "C_MORE"));
+        assert!(table.routes.is_empty());
+
+        table.add(&RouteScope::Default, "C_DEFAULT");
+
+        assert!(!table.remove(&RouteScope::Default, "C_OTHER"));
+        assert!(table.remove(&RouteScope::Default, "C_DEFAULT"));
+        assert_eq!(None, table.default_channel);
+    }
+}
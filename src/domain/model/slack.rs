@@ -1,32 +1,667 @@
+use std::{collections::HashMap, fmt};
+use regex::Regex;
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize)]
+use crate::domain::model::{
+    block_kit::{Attachment, Block, Text, MAX_MESSAGE_BLOCKS, MAX_MESSAGE_CHARACTERS},
+    github::IssueReference,
+    mrkdwn::USER_LINK_MARKER,
+};
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Message {
-    pub blocks: Vec<Blocks>,
+    pub blocks: Vec<Block>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
     pub response_type: String,
+    /// Shown in notifications and by clients that cannot render blocks.
+    #[serde(default)]
+    pub text: String,
+}
+
+impl Message {
+    /// Lays the message out within Slack's limits: long sections are split, and blocks that do not
+    /// fit into one message go into follow-up messages, to be posted in its thread. Attachments stay
+    /// on the first page; blocks beyond what one attachment holds follow as plain blocks.
+    pub fn pages(self) -> Vec<Message> {
+        let mut overflow = vec![];
+        let attachments = self
+            .attachments
+            .into_iter()
+            .map(|attachment| {
+                let mut blocks: Vec<Block> = attachment.blocks.into_iter().flat_map(Block::split).collect();
+
+                if blocks.len() > MAX_MESSAGE_BLOCKS {
+                    overflow.extend(blocks.split_off(MAX_MESSAGE_BLOCKS));
+                }
+
+                Attachment { blocks, ..attachment }
+            })
+            .collect::<Vec<_>>();
+
+        let mut characters = serde_json::to_string(&attachments).map(|json| json.chars().count()).unwrap_or(0);
+        let mut attachments = Some(attachments);
+        let mut pages: Vec<Vec<Block>> = vec![vec![]];
+
+        for block in self.blocks.into_iter().flat_map(Block::split).chain(overflow) {
+            let block_characters = serde_json::to_string(&block).map(|json| json.chars().count()).unwrap_or(0);
+            let page = pages.last_mut().unwrap();
+
+            if !page.is_empty() && (page.len() == MAX_MESSAGE_BLOCKS || characters + block_characters > MAX_MESSAGE_CHARACTERS) {
+                pages.push(vec![]);
+                characters = 0;
+            }
+
+            pages.last_mut().unwrap().push(block);
+            characters += block_characters;
+        }
+
+        pages
+            .into_iter()
+            .map(|blocks| Message {
+                blocks,
+                attachments: attachments.take().unwrap_or_default(),
+                response_type: self.response_type.to_string(),
+                text: self.text.to_string(),
+            })
+            .collect()
+    }
+
+    fn github_user_links() -> Regex {
+        Regex::new(&format!(r"<https://github\.com/(?P<path>[A-Za-z0-9-]+)/?\|(?P<login>[A-Za-z0-9-]+){}>", USER_LINK_MARKER)).unwrap()
+    }
+
+    fn all_blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter().chain(self.attachments.iter().flat_map(|attachment| attachment.blocks.iter()))
+    }
+
+    /// Logins of the GitHub users linked in the message by `user_link`.
+    pub fn github_logins(&self) -> Vec<String> {
+        let re = Self::github_user_links();
+        let mut logins: Vec<String> = vec![];
+
+        for text in self.all_blocks().flat_map(Block::mrkdwn_texts) {
+            for caps in re.captures_iter(text) {
+                let login = caps["login"].to_lowercase();
+
+                if caps["path"].eq_ignore_ascii_case(&login) && !logins.contains(&login) {
+                    logins.push(login);
+                }
+            }
+        }
+
+        logins
+    }
+
+    /// Replaces links rendered by `user_link` with mentions of the users' Slack accounts, keyed by lowercase
+    /// login. Links to GitHub users out of untrusted text are left alone.
+    pub fn mention_users(mut self, slack_users: &HashMap<String, String>) -> Message {
+        let re = Self::github_user_links();
+
+        let blocks = self.blocks.iter_mut().chain(self.attachments.iter_mut().flat_map(|attachment| attachment.blocks.iter_mut()));
+
+        for text in blocks.flat_map(Block::mrkdwn_texts_mut) {
+            *text = re
+                .replace_all(text, |caps: &regex::Captures| {
+                    let login = caps["login"].to_lowercase();
+
+                    match slack_users.get(&login) {
+                        Some(user_id) if caps["path"].eq_ignore_ascii_case(&login) => format!("<@{}>", user_id),
+                        _ => caps[0].to_string(),
+                    }
+                })
+                .to_string();
+        }
+
+        self
+    }
+}
+
+#[derive(Serialize)]
+pub struct PostMessageRequest {
+    pub channel: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<Block>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_broadcast: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct UpdateMessageRequest {
+    pub channel: String,
+    pub ts: String,
+    pub blocks: Vec<Block>,
+    pub attachments: Vec<Attachment>,
+    pub text: String,
+}
+
+/// A message posted through the Web API, which can later be updated, deleted or replied to.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PostedMessage {
+    pub channel: String,
+    pub ts: String,
+}
+
+#[derive(Serialize)]
+pub struct ReactionRequest {
+    pub channel: String,
+    pub timestamp: String,
+    pub name: String,
+}
+
+/// Reactions to put on and take off a pull request announcement after a review event.
+#[derive(PartialEq, Debug, Default)]
+pub struct ReactionChange {
+    pub add: Vec<&'static str>,
+    pub remove: Vec<&'static str>,
+}
+
+/// A card posted in a workspace, kept so it can be re-rendered when its issue or pull request changes.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct IndexedMessage {
+    pub team_id: String,
+    pub message: PostedMessage,
+}
+
+/// Where notifications are delivered: a channel through the Web API, or a legacy incoming webhook.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum MessageSink {
+    Channel {
+        team_id: String,
+        channel: String,
+    },
+    IncomingWebhook(String),
+}
+
+/// The webhook URL is a credential, so it is never spelled out.
+impl fmt::Display for MessageSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageSink::Channel { team_id, channel } => write!(f, "{} in {}", channel, team_id),
+            MessageSink::IncomingWebhook(_) => write!(f, "the incoming webhook"),
+        }
+    }
+}
+
+/// Slack answers with channel IDs (`C123ABC456`) even when a message was posted to a name such as `#dev`.
+pub fn is_channel_id(channel: &str) -> bool {
+    channel.len() >= 9
+        && channel.starts_with(['C', 'G', 'D'])
+        && channel.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+#[derive(Serialize)]
+pub struct UnfurlRequest {
+    pub channel: String,
+    pub ts: String,
+    pub unfurls: HashMap<String, Unfurl>,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Unfurl {
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Deserialize)]
+pub struct ApiResponse {
+    pub ok: bool,
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
-pub struct Blocks {
+pub struct View {
     pub r#type: String,
-    pub text: Text,
-    pub accessory: Accessory,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submit: Option<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_metadata: Option<String>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Default)]
+pub struct HomeViewBuilder {
+    blocks: Vec<Block>,
+}
+
+impl HomeViewBuilder {
+    pub fn header(mut self, text: &str) -> Self {
+        self.blocks.push(Block::header(text));
+
+        self
+    }
+
+    pub fn section(mut self, mrkdwn: &str) -> Self {
+        self.blocks.push(Block::section(Text::mrkdwn(mrkdwn)));
+
+        self
+    }
+
+    pub fn divider(mut self) -> Self {
+        self.blocks.push(Block::divider());
+
+        self
+    }
+
+    pub fn build(self) -> View {
+        View {
+            r#type: "home".to_string(),
+            callback_id: None,
+            title: None,
+            submit: None,
+            private_metadata: None,
+            blocks: self.blocks,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ViewsPublishRequest {
+    pub user_id: String,
+    pub view: View,
+}
+
+#[derive(Serialize)]
+pub struct ViewsOpenRequest {
+    pub trigger_id: String,
+    pub view: View,
+}
+
+#[derive(Deserialize)]
+pub struct ViewsOpenResponse {
+    pub view: OpenedView,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct OpenedView {
+    pub id: String,
+    pub hash: String,
+}
+
+#[derive(Serialize)]
+pub struct ViewsUpdateRequest {
+    pub view_id: String,
+    pub hash: String,
+    pub view: View,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ViewSubmissionResponse {
+    pub response_action: String,
+    pub errors: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+pub struct OAuthAccessResponse {
+    pub access_token: String,
+    pub bot_user_id: String,
+    pub scope: String,
+    pub team: OAuthTeam,
+    pub authed_user: OAuthUser,
+    pub incoming_webhook: Option<IncomingWebhook>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct OAuthTeam {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct OAuthUser {
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct IncomingWebhook {
+    pub channel_id: String,
+    pub url: String,
+}
+
+/// A workspace the app is installed to, keyed by `team_id` in the store.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Installation {
+    pub team_id: String,
+    pub team_name: String,
+    pub bot_token: String,
+    pub bot_user_id: String,
+    pub scope: String,
+    pub installed_by: String,
+    pub installed_at: u64,
+    pub incoming_webhook: Option<IncomingWebhook>,
 }
 
+impl Installation {
+    pub fn from_oauth(res: OAuthAccessResponse, installed_at: u64) -> Installation {
+        Installation {
+            team_id: res.team.id,
+            team_name: res.team.name,
+            bot_token: res.access_token,
+            bot_user_id: res.bot_user_id,
+            scope: res.scope,
+            installed_by: res.authed_user.id,
+            installed_at,
+            incoming_webhook: res.incoming_webhook,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PermalinkResponse {
+    pub permalink: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConversationsOpenResponse {
+    pub channel: InteractionChannel,
+}
+
+#[derive(Deserialize)]
+pub struct UserInfoResponse {
+    pub user: SlackUser,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct SlackUser {
+    pub id: String,
+    pub name: String,
+    pub real_name: Option<String>,
+    pub profile: SlackUserProfile,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub is_owner: bool,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct SlackUserProfile {
+    pub email: Option<String>,
+}
+
+/// Where a "Create issue" modal was opened from, carried through the modal as `private_metadata`.
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
-pub struct Text {
-    pub r#type: String,
+pub struct ShortcutOrigin {
+    pub channel: String,
+    pub thread_ts: String,
+}
+
+#[derive(Serialize)]
+pub struct ResponseUrlMessage {
+    pub replace_original: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_type: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<Block>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InteractionPayload {
+    BlockActions(BlockActionsPayload),
+    MessageAction(MessageActionPayload),
+    ViewSubmission(ViewSubmissionPayload),
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct BlockActionsPayload {
+    pub user: InteractionUser,
+    pub team: Option<InteractionTeam>,
+    pub response_url: Option<String>,
+    pub container: Option<InteractionContainer>,
+    pub actions: Vec<BlockAction>,
+    pub view: Option<ViewPayload>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct InteractionContainer {
+    pub channel_id: Option<String>,
+    pub message_ts: Option<String>,
+    #[serde(default)]
+    pub is_ephemeral: bool,
+}
+
+impl InteractionContainer {
+    /// The channel message holding the clicked card; ephemeral messages cannot be edited through the Web API.
+    pub fn message(&self) -> Option<PostedMessage> {
+        match (&self.channel_id, &self.message_ts) {
+            (Some(channel), Some(ts)) if !self.is_ephemeral => Some(PostedMessage {
+                channel: channel.to_string(),
+                ts: ts.to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct MessageActionPayload {
+    pub callback_id: String,
+    pub trigger_id: String,
+    pub user: InteractionUser,
+    pub team: Option<InteractionTeam>,
+    pub channel: InteractionChannel,
+    pub message: ShortcutMessage,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct InteractionChannel {
+    pub id: String,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct ShortcutMessage {
     pub text: String,
+    pub user: Option<String>,
+    pub ts: String,
+    pub thread_ts: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
-pub struct Accessory {
-    pub r#type: String,
-    pub image_url: String,
-    pub alt_text: String,
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct ViewSubmissionPayload {
+    pub user: InteractionUser,
+    pub team: Option<InteractionTeam>,
+    pub view: ViewPayload,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct ViewPayload {
+    pub id: String,
+    pub hash: String,
+    pub callback_id: String,
+    pub private_metadata: String,
+    pub state: ViewState,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct ViewState {
+    pub values: HashMap<String, HashMap<String, ViewStateValue>>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct ViewStateValue {
+    pub value: Option<String>,
+    pub selected_option: Option<SelectedOption>,
+    pub selected_options: Option<Vec<SelectedOption>>,
+}
+
+impl ViewState {
+    fn value(&self, block_id: &str) -> Option<&ViewStateValue> {
+        self.values.get(block_id)?.values().next()
+    }
+
+    pub fn text(&self, block_id: &str) -> Option<&str> {
+        self.value(block_id)?.value.as_deref()
+    }
+
+    pub fn selected(&self, block_id: &str) -> Option<&str> {
+        Some(self.value(block_id)?.selected_option.as_ref()?.value.as_str())
+    }
+
+    pub fn selected_all(&self, block_id: &str) -> Vec<&str> {
+        self.value(block_id)
+            .and_then(|value| value.selected_options.as_ref())
+            .map(|options| options.iter().map(|option| option.value.as_str()).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct InteractionUser {
+    pub id: String,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct InteractionTeam {
+    pub id: String,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct BlockAction {
+    pub action_id: String,
+    pub value: Option<String>,
+    pub selected_option: Option<SelectedOption>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct SelectedOption {
+    pub value: String,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum CardAction {
+    Close(IssueReference),
+    Reopen(IssueReference),
+    AssignToMe(IssueReference),
+    AddLabel(IssueReference, String),
+}
+
+impl CardAction {
+    pub fn reference(&self) -> &IssueReference {
+        match self {
+            CardAction::Close(reference)
+            | CardAction::Reopen(reference)
+            | CardAction::AssignToMe(reference)
+            | CardAction::AddLabel(reference, _) => reference,
+        }
+    }
+}
+
+impl BlockAction {
+    /// Buttons carry the `owner/repo#number` reference as their value, label options carry `owner/repo#number:label`.
+    pub fn card_action(&self) -> Option<CardAction> {
+        match self.action_id.as_str() {
+            "close" => IssueReference::parse(self.value.as_deref()?).map(CardAction::Close),
+            "reopen" => IssueReference::parse(self.value.as_deref()?).map(CardAction::Reopen),
+            "assign_to_me" => IssueReference::parse(self.value.as_deref()?).map(CardAction::AssignToMe),
+            "add_label" => {
+                let (reference, label) = self.selected_option.as_ref()?.value.split_once(':')?;
+
+                IssueReference::parse(reference).map(|reference| CardAction::AddLabel(reference, label.to_string()))
+            },
+            _ => None,
+        }
+    }
 }
 
 pub struct TextLine {
     pub title: String,
     pub state: String,
 }
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SlackEvent {
+    Message(MessageEvent),
+    AppMention(AppMentionEvent),
+    LinkShared(LinkSharedEvent),
+    AppHomeOpened(AppHomeOpenedEvent),
+    AppUninstalled,
+    TokensRevoked(TokensRevokedEvent),
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct MessageEvent {
+    pub channel: String,
+    pub user: Option<String>,
+    pub text: Option<String>,
+    pub ts: String,
+    pub thread_ts: Option<String>,
+    pub subtype: Option<String>,
+    pub bot_id: Option<String>,
+}
+
+impl MessageEvent {
+    /// Plain messages written by a person; edits, deletions and bot posts all carry a subtype or a `bot_id`.
+    pub fn is_user_message(&self) -> bool {
+        self.subtype.is_none() && self.bot_id.is_none() && self.user.is_some()
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct AppMentionEvent {
+    pub channel: String,
+    pub user: String,
+    pub text: String,
+    pub ts: String,
+    pub thread_ts: Option<String>,
+}
+
+impl AppMentionEvent {
+    pub fn text_without_mentions(&self) -> String {
+        let re = Regex::new(r"<@[A-Z0-9]+(\|[^>]*)?>").unwrap();
+
+        re.replace_all(&self.text, "").trim().to_string()
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct LinkSharedEvent {
+    pub channel: String,
+    pub user: String,
+    pub message_ts: String,
+    pub links: Vec<SharedLink>,
+    pub unfurl_id: Option<String>,
+    pub source: Option<String>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct AppHomeOpenedEvent {
+    pub user: String,
+    pub tab: String,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct TokensRevokedEvent {
+    pub tokens: RevokedTokens,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct RevokedTokens {
+    #[serde(default)]
+    pub oauth: Vec<String>,
+    #[serde(default)]
+    pub bot: Vec<String>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct SharedLink {
+    pub domain: String,
+    pub url: String,
+}
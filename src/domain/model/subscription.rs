@@ -0,0 +1,228 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+use crate::domain::model::{
+    digest::Frequency,
+    filter::{Filter, Rule},
+};
+
+/// Groups of webhook events a channel can subscribe to.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Issues,
+    Pulls,
+    Reviews,
+    Comments,
+    Releases,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Issues,
+        Category::Pulls,
+        Category::Reviews,
+        Category::Comments,
+        Category::Releases,
+    ];
+
+    pub fn parse(name: &str) -> Option<Category> {
+        Self::ALL.into_iter().find(|category| category.to_string() == name)
+    }
+
+    /// The category of a webhook event type as sent in `X-GitHub-Event`. CI events are not
+    /// notified about, so they have none.
+    pub fn from_event(event: &str) -> Option<Category> {
+        match event {
+            "issues" => Some(Category::Issues),
+            "pull_request" => Some(Category::Pulls),
+            "pull_request_review" | "pull_request_review_comment" => Some(Category::Reviews),
+            "issue_comment" | "commit_comment" => Some(Category::Comments),
+            "release" => Some(Category::Releases),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Category::Issues => "issues",
+            Category::Pulls => "pulls",
+            Category::Reviews => "reviews",
+            Category::Comments => "comments",
+            Category::Releases => "releases",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Subscription {
+    /// `owner/repo`, compared case-insensitively like GitHub does.
+    pub repository: String,
+    pub categories: Vec<Category>,
+    #[serde(default)]
+    pub filter: Filter,
+    /// Collects events for a periodic digest instead of posting them as they happen.
+    #[serde(default)]
+    pub digest: Option<Frequency>,
+}
+
+impl Subscription {
+    pub fn wants(&self, category: Category) -> bool {
+        self.categories.contains(&category)
+    }
+}
+
+/// What one channel of one workspace receives.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ChannelSubscriptions {
+    #[serde(default)]
+    pub subscriptions: Vec<Subscription>,
+}
+
+/// A channel subscribed to a repository, indexed by repository so `webhook` finds it without a scan.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Subscriber {
+    pub team_id: String,
+    pub channel: String,
+}
+
+impl ChannelSubscriptions {
+    pub fn subscribe(&mut self, repository: &str, categories: &[Category]) {
+        match self.subscriptions.iter_mut().find(|subscription| subscription.repository.eq_ignore_ascii_case(repository)) {
+            Some(subscription) => {
+                for category in categories {
+                    if !subscription.categories.contains(category) {
+                        subscription.categories.push(*category);
+                    }
+                }
+            },
+            None => self.subscriptions.push(Subscription {
+                repository: repository.to_string(),
+                categories: categories.to_vec(),
+                filter: Filter::default(),
+                digest: None,
+            }),
+        }
+    }
+
+    /// Drops `categories` of `repository`, or the whole subscription when `categories` is empty.
+    /// Returns whether the channel is still subscribed to the repository.
+    pub fn unsubscribe(&mut self, repository: &str, categories: &[Category]) -> bool {
+        for subscription in self.subscriptions.iter_mut().filter(|subscription| subscription.repository.eq_ignore_ascii_case(repository)) {
+            subscription.categories.retain(|category| !categories.is_empty() && !categories.contains(category));
+        }
+
+        self.subscriptions.retain(|subscription| !subscription.categories.is_empty());
+
+        self.subscription(repository).is_some()
+    }
+
+    /// Adds `rules` to the filter of `repository`, or clears it when `rules` is empty.
+    /// Returns whether the channel is subscribed to the repository at all.
+    pub fn set_filter(&mut self, repository: &str, rules: &[Rule]) -> bool {
+        let subscription = match self.subscriptions.iter_mut().find(|subscription| subscription.repository.eq_ignore_ascii_case(repository)) {
+            Some(subscription) => subscription,
+            None => return false,
+        };
+
+        match rules {
+            [] => subscription.filter.rules.clear(),
+            rules => {
+                for rule in rules {
+                    if !subscription.filter.rules.contains(rule) {
+                        subscription.filter.rules.push(rule.clone());
+                    }
+                }
+            },
+        }
+
+        true
+    }
+
+    /// Switches `repository` between digests and real-time delivery, which `None` stands for.
+    /// Returns whether the channel is subscribed to the repository at all.
+    pub fn set_digest(&mut self, repository: &str, digest: Option<Frequency>) -> bool {
+        match self.subscriptions.iter_mut().find(|subscription| subscription.repository.eq_ignore_ascii_case(repository)) {
+            Some(subscription) => {
+                subscription.digest = digest;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn subscription(&self, repository: &str) -> Option<&Subscription> {
+        self.subscriptions.iter().find(|subscription| subscription.repository.eq_ignore_ascii_case(repository))
+    }
+}
+
+#[cfg(test)]
+mod subscription_test {
+    use super::*;
+
+    #[test]
+    fn test_category() {
+        assert_eq!(Some(Category::Pulls), Category::parse("pulls"));
+        assert_eq!(None, Category::parse("pushes"));
+        assert_eq!(Some(Category::Pulls), Category::from_event("pull_request"));
+        assert_eq!(Some(Category::Reviews), Category::from_event("pull_request_review_comment"));
+        assert_eq!(None, Category::parse("ci"));
+        assert_eq!(None, Category::from_event("workflow_run"));
+        assert_eq!(None, Category::from_event("push"));
+    }
+
+    #[test]
+    fn test_subscribe_and_unsubscribe() {
+        let wants = |subscriptions: &ChannelSubscriptions, repository: &str, category| subscriptions.subscription(repository).is_some_and(|subscription| subscription.wants(category));
+        let mut subscriptions = ChannelSubscriptions::default();
+        subscriptions.subscribe("org/api", &[Category::Issues]);
+        subscriptions.subscribe("Org/API", &[Category::Issues, Category::Pulls]);
+
+        assert_eq!(1, subscriptions.subscriptions.len());
+        assert!(wants(&subscriptions, "org/api", Category::Pulls));
+        assert!(!wants(&subscriptions, "org/api", Category::Reviews));
+        assert!(!wants(&subscriptions, "org/web", Category::Issues));
+
+        assert!(subscriptions.unsubscribe("org/api", &[Category::Issues]));
+        assert!(!wants(&subscriptions, "org/api", Category::Issues));
+        assert!(!subscriptions.unsubscribe("org/api", &[]));
+        assert!(subscriptions.subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_set_digest() {
+        let mut subscriptions = ChannelSubscriptions::default();
+
+        assert!(!subscriptions.set_digest("org/api", Some(Frequency::Daily)));
+
+        subscriptions.subscribe("org/api", &[Category::Issues]);
+
+        assert!(subscriptions.set_digest("Org/API", Some(Frequency::Weekly)));
+        assert_eq!(Some(Frequency::Weekly), subscriptions.subscription("org/api").unwrap().digest);
+        assert!(subscriptions.set_digest("org/api", None));
+        assert_eq!(None, subscriptions.subscription("org/api").unwrap().digest);
+    }
+
+    #[test]
+    fn test_set_filter() {
+        let mut subscriptions = ChannelSubscriptions::default();
+        let rule = Rule::parse("+path:infra/**").unwrap();
+
+        assert!(!subscriptions.set_filter("org/api", &[rule.clone()]));
+
+        subscriptions.subscribe("org/api", &[Category::Pulls]);
+
+        assert!(subscriptions.set_filter("Org/API", &[rule.clone(), rule.clone()]));
+        assert_eq!(vec![rule], subscriptions.subscription("org/api").unwrap().filter.rules);
+
+        subscriptions.subscribe("org/api", &[Category::Issues]);
+
+        assert_eq!(1, subscriptions.subscription("org/api").unwrap().filter.rules.len());
+        assert!(subscriptions.set_filter("org/api", &[]));
+        assert!(subscriptions.subscription("org/api").unwrap().filter.rules.is_empty());
+    }
+}
@@ -1 +1,9 @@
+pub mod delivery;
+pub mod events;
+pub mod identity;
+pub mod installation;
+pub mod interactions;
+pub mod quiet_hours;
+pub mod rollups;
 pub mod routes;
+pub mod subscriptions;
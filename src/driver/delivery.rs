@@ -0,0 +1,401 @@
+use std::error::Error;
+use worker::*;
+use worker::kv::KvStore;
+
+use crate::adapter::{
+    github::Github,
+    slack::Slack,
+    store::Store,
+};
+use crate::domain::model::{
+    digest::Frequency,
+    github::{IssueReference, Label},
+    routes::{AlertWebhookRequest, DirectNotification, GithubWebhookRequest, Release},
+    slack::{is_channel_id, IndexedMessage, Message, MessageSink, PostedMessage, ReactionChange},
+};
+use crate::driver::{
+    identity::mention_users,
+    installation::{self, bot_token, workspaces},
+    subscriptions::subscribed_channels,
+};
+
+/// Handles a GitHub event for `reference`: cards already posted for the item are refreshed
+/// before the event itself is delivered to `sinks` and reflected as reactions. A failing sink
+/// does not keep the others from their notification; the failures are reported together.
+pub async fn publish(
+    body: &GithubWebhookRequest,
+    reference: &IssueReference,
+    sinks: &[MessageSink],
+    ctx: &RouteContext<Context>,
+) -> std::result::Result<(), Box<dyn Error>> {
+    let mut errors: Vec<String> = vec![];
+
+    if let Err(e) = refresh_cards(body, reference, ctx).await {
+        errors.push(format!("refreshing cards: {}", e));
+    }
+
+    for sink in sinks {
+        let delivered = match deliver_notification(sink, body, reference, ctx).await {
+            Ok(_) => match body.reactions() {
+                Some(change) => react(sink, reference, &change, ctx).await,
+                None => Ok(()),
+            },
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = delivered {
+            errors.push(format!("{}: {}", sink, e));
+        }
+    }
+
+    aggregate(errors)
+}
+
+/// Releases have no thread or card to follow, so they are announced once to every sink.
+pub async fn publish_release(body: &GithubWebhookRequest, release: &Release, sinks: &[MessageSink], ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let mut errors: Vec<String> = vec![];
+
+    for sink in sinks {
+        if let Err(e) = deliver(sink, Slack.create_release_message(body, release), &ctx.env).await {
+            errors.push(format!("{}: {}", sink, e));
+        }
+    }
+
+    aggregate(errors)
+}
+
+/// Urgent alerts skip quiet hours, digests and subscription filters: every channel subscribed to
+/// the repository gets them, and so do the channels the `SLACK_TEAM_ID` workspace routes them to.
+pub async fn publish_alert(alert: &AlertWebhookRequest, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let message = match Slack.create_alert_message(alert) {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let var = |name: &str| ctx.var(name).map(|value| value.to_string()).ok().filter(|value| !value.is_empty());
+    let kv = ctx.kv("STORE")?;
+    let repository = alert.full_name().unwrap_or_default();
+    let mut sinks: Vec<MessageSink> = vec![];
+
+    if !repository.is_empty() {
+        for (subscriber, _) in Store.subscribers(&kv, &repository).await? {
+            sinks.push(MessageSink::Channel { team_id: subscriber.team_id, channel: subscriber.channel });
+        }
+    }
+
+    if let Some(team_id) = var("SLACK_TEAM_ID") {
+        let table = Store.routing_table(&kv, &team_id).await?;
+
+        for channel in table.channels(&repository, alert.event(), var("SLACK_CHANNEL").as_deref()) {
+            let sink = MessageSink::Channel { team_id: team_id.to_string(), channel };
+
+            if !sinks.contains(&sink) {
+                sinks.push(sink);
+            }
+        }
+    }
+
+    if sinks.is_empty() {
+        sinks.push(MessageSink::IncomingWebhook(ctx.secret("SLACK_WEBHOOK_URL")?.to_string()));
+    }
+
+    let mut errors: Vec<String> = vec![];
+
+    for sink in &sinks {
+        if let Err(e) = deliver(sink, message.clone(), &ctx.env).await {
+            errors.push(format!("{}: {}", sink, e));
+        }
+    }
+
+    aggregate(errors)
+}
+
+fn aggregate(errors: Vec<String>) -> std::result::Result<(), Box<dyn Error>> {
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("; ").into()),
+    }
+}
+
+/// DMs linked users who were asked to review, assigned or mentioned, unless they opted out.
+/// Every workspace the bot is in is searched for linked accounts, wherever the event itself is
+/// posted. A failing workspace does not keep the others from their messages.
+pub async fn send_direct_messages(body: &GithubWebhookRequest, reference: &IssueReference, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let notifications = body.direct_notifications();
+
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    let mut errors: Vec<String> = vec![];
+
+    for team_id in workspaces(&ctx.env).await? {
+        if let Err(e) = send_workspace_direct_messages(&team_id, body, reference, &notifications, ctx).await {
+            errors.push(format!("direct messages in {}: {}", team_id, e));
+        }
+    }
+
+    aggregate(errors)
+}
+
+async fn send_workspace_direct_messages(
+    team_id: &str,
+    body: &GithubWebhookRequest,
+    reference: &IssueReference,
+    notifications: &[DirectNotification],
+    ctx: &RouteContext<Context>,
+) -> std::result::Result<(), Box<dyn Error>> {
+    let kv = ctx.kv("STORE")?;
+    let mut bot_token = None;
+
+    for notification in notifications {
+        let user_id = match Store.slack_user(&kv, team_id, &notification.login).await? {
+            Some(user_id) => user_id,
+            None => continue,
+        };
+
+        if !Store.direct_messages_enabled(&kv, team_id, &user_id).await? {
+            continue;
+        }
+
+        let message = match (&body.issue, &body.pull_request) {
+            (Some(issue), None) => Slack.create_direct_message(issue, reference, body, &notification.reason),
+            (None, Some(pr)) => Slack.create_direct_message(pr, reference, body, &notification.reason),
+            _ => continue,
+        };
+
+        // Workspaces without linked recipients never need their token.
+        let bot_token = match &bot_token {
+            Some(bot_token) => bot_token,
+            None => bot_token.insert(installation::bot_token(team_id, &ctx.env).await?),
+        };
+
+        let channel = Slack.open_conversation(bot_token, &user_id).await?;
+        Slack.post_message(bot_token, &channel, None, mention_users(team_id, message, &ctx.env).await?).await?;
+    }
+
+    Ok(())
+}
+
+/// Notifications go to the channels subscribed to the event and to those the `SLACK_TEAM_ID`
+/// workspace routes it to. Without subscriptions, the default channel or `SLACK_CHANNEL` gets it,
+/// and without a workspace the `SLACK_WEBHOOK_URL` incoming webhook. Events every subscription
+/// filtered out and no route matches go nowhere. Channels subscribed in digest mode are returned
+/// apart, with their digest, for the caller to record the event for once the bot policy allows it.
+pub async fn notification_sinks(body: &GithubWebhookRequest, ctx: &RouteContext<Context>) -> std::result::Result<(Vec<MessageSink>, Vec<(MessageSink, Frequency)>), Box<dyn Error>> {
+    let var = |name: &str| ctx.var(name).map(|value| value.to_string()).ok().filter(|value| !value.is_empty());
+    let repository = body.full_name();
+    let subscribed = subscribed_channels(body, ctx).await?;
+    let has_subscriptions = subscribed.is_some();
+    let mut sinks: Vec<MessageSink> = vec![];
+    let mut digests: Vec<(MessageSink, Frequency)> = vec![];
+
+    for (subscriber, digest) in subscribed.unwrap_or_default() {
+        let sink = MessageSink::Channel { team_id: subscriber.team_id, channel: subscriber.channel };
+
+        match digest {
+            Some(frequency) => digests.push((sink, frequency)),
+            None => sinks.push(sink),
+        }
+    }
+
+    if let Some(team_id) = var("SLACK_TEAM_ID") {
+        let table = Store.routing_table(&ctx.kv("STORE")?, &team_id).await?;
+        let channels = match has_subscriptions {
+            true => table.routed(&repository, body.event()),
+            false => table.channels(&repository, body.event(), var("SLACK_CHANNEL").as_deref()),
+        };
+
+        for channel in channels {
+            let sink = MessageSink::Channel { team_id: team_id.to_string(), channel };
+
+            if !sinks.contains(&sink) {
+                sinks.push(sink);
+            }
+        }
+    }
+
+    if sinks.is_empty() && !has_subscriptions {
+        sinks.push(MessageSink::IncomingWebhook(ctx.secret("SLACK_WEBHOOK_URL")?.to_string()));
+    }
+
+    Ok((sinks, digests))
+}
+
+/// Incoming webhooks do not tell where the message landed, so only Web API deliveries return it.
+pub async fn deliver(sink: &MessageSink, message: Message, env: &Env) -> std::result::Result<Option<PostedMessage>, Box<dyn Error>> {
+    match sink {
+        MessageSink::Channel { team_id, channel } => {
+            let message = mention_users(team_id, message, env).await?;
+            let posted = Slack.post_message(&bot_token(team_id, env).await?, channel, None, message).await?;
+
+            Ok(Some(posted))
+        },
+        MessageSink::IncomingWebhook(url) => {
+            Slack.send_message(url, message).await?;
+
+            Ok(None)
+        },
+    }
+}
+
+/// The first notification for an item is posted as a card, later ones go as a reply into its thread.
+/// Incoming webhooks cannot thread, so they keep receiving the full card.
+pub async fn deliver_notification(
+    sink: &MessageSink,
+    body: &GithubWebhookRequest,
+    reference: &IssueReference,
+    ctx: &RouteContext<Context>,
+) -> std::result::Result<Option<PostedMessage>, Box<dyn Error>> {
+    let (team_id, channel) = match sink {
+        MessageSink::Channel { team_id, channel } => (team_id, channel),
+        MessageSink::IncomingWebhook(_) => return deliver(sink, notification_card(body, ctx).await?, &ctx.env).await,
+    };
+
+    let kv = ctx.kv("STORE")?;
+    let bot_token = bot_token(team_id, &ctx.env).await?;
+
+    let thread = match channel_id(&kv, team_id, channel).await? {
+        Some(channel_id) => Store.thread(&kv, &channel_id, reference).await?,
+        None => None,
+    };
+
+    match thread {
+        Some(thread) => {
+            let broadcast = body.is_important() && broadcast_enabled(ctx);
+            let reply = mention_users(team_id, thread_reply(body)?, &ctx.env).await?;
+            let posted = Slack.post_reply(&bot_token, &thread, reply, broadcast).await?;
+
+            Ok(Some(posted))
+        },
+        None => {
+            let card = mention_users(team_id, notification_card(body, ctx).await?, &ctx.env).await?;
+            let posted = Slack.post_message(&bot_token, channel, None, card).await?;
+            Store.save_thread(&kv, reference, &posted).await?;
+
+            if posted.channel != *channel {
+                Store.save_channel_id(&kv, team_id, channel.trim_start_matches('#'), &posted.channel).await?;
+            }
+
+            index_card(team_id, reference, &posted, &ctx.env).await?;
+
+            Ok(Some(posted))
+        },
+    }
+}
+
+pub async fn index_card(team_id: &str, reference: &IssueReference, posted: &PostedMessage, env: &Env) -> std::result::Result<(), Box<dyn Error>> {
+    let message = IndexedMessage {
+        team_id: team_id.to_string(),
+        message: posted.clone(),
+    };
+
+    Store.index_message(&env.kv("STORE")?, reference, message).await
+}
+
+/// Swaps every indexed card for one showing the item as in `body`. A card deleted in Slack must not hold back the others.
+pub async fn refresh_cards(body: &GithubWebhookRequest, reference: &IssueReference, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let indexed = Store.indexed_messages(&ctx.kv("STORE")?, reference).await?;
+
+    if indexed.is_empty() {
+        return Ok(());
+    }
+
+    let labels = repository_labels(body, ctx).await?;
+    let card = match (&body.issue, &body.pull_request) {
+        (Some(issue), None) => Slack.create_card(issue, reference, &labels),
+        (None, Some(pr)) => Slack.create_card(pr, reference, &labels),
+        _ => return Err("Unknown payload".into()),
+    };
+
+    for indexed in indexed {
+        if let Ok(bot_token) = bot_token(&indexed.team_id, &ctx.env).await {
+            let card = mention_users(&indexed.team_id, card.clone(), &ctx.env).await?;
+            let _ = Slack.update_message(&bot_token, &indexed.message, card).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the cards of an issue deleted on GitHub, together with what was tracked about them.
+pub async fn delete_cards(reference: &IssueReference, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let kv = ctx.kv("STORE")?;
+
+    for indexed in Store.indexed_messages(&kv, reference).await? {
+        if let Ok(bot_token) = bot_token(&indexed.team_id, &ctx.env).await {
+            let _ = Slack.delete_message(&bot_token, &indexed.message).await;
+        }
+
+        Store.delete_thread(&kv, &indexed.message.channel, reference).await?;
+    }
+
+    Store.delete_message_index(&kv, reference).await
+}
+
+/// Reactions go on the announcement the item's thread hangs off; incoming webhooks have none.
+pub async fn react(sink: &MessageSink, reference: &IssueReference, change: &ReactionChange, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let (team_id, channel) = match sink {
+        MessageSink::Channel { team_id, channel } => (team_id, channel),
+        MessageSink::IncomingWebhook(_) => return Ok(()),
+    };
+
+    let kv = ctx.kv("STORE")?;
+
+    let thread = match channel_id(&kv, team_id, channel).await? {
+        Some(channel_id) => Store.thread(&kv, &channel_id, reference).await?,
+        None => None,
+    };
+    let thread = match thread {
+        Some(thread) => thread,
+        None => return Ok(()),
+    };
+    let bot_token = bot_token(team_id, &ctx.env).await?;
+
+    for name in &change.remove {
+        Slack.remove_reaction(&bot_token, &thread, name).await?;
+    }
+    for name in &change.add {
+        Slack.add_reaction(&bot_token, &thread, name).await?;
+    }
+
+    Ok(())
+}
+
+async fn notification_card(body: &GithubWebhookRequest, ctx: &RouteContext<Context>) -> std::result::Result<Message, Box<dyn Error>> {
+    let labels = repository_labels(body, ctx).await?;
+
+    match (&body.issue, &body.pull_request) {
+        (Some(issue), None) => Ok(Slack.create_message(issue, body, &labels)),
+        (None, Some(pr)) => Ok(Slack.create_message(pr, body, &labels)),
+        _ => Err("Unknown payload".into()),
+    }
+}
+
+fn thread_reply(body: &GithubWebhookRequest) -> std::result::Result<Message, Box<dyn Error>> {
+    match (&body.issue, &body.pull_request) {
+        (Some(issue), None) => Ok(Slack.create_thread_reply(issue, body)),
+        (None, Some(pr)) => Ok(Slack.create_thread_reply(pr, body)),
+        _ => Err("Unknown payload".into()),
+    }
+}
+
+/// Labels for the card's label picker, only fetched once a card is actually built. Without them the card offers none.
+async fn repository_labels(body: &GithubWebhookRequest, ctx: &RouteContext<Context>) -> std::result::Result<Vec<Label>, Box<dyn Error>> {
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+
+    Ok(Github.fetch_labels(&github_token, &body.repository.owner.login, &body.repository.name).await.unwrap_or_default())
+}
+
+/// Threads are kept under the channel ID Slack answers with, while sinks may name their channel.
+/// Names resolve to the ID saved when the bot first posted there; before that nothing was threaded.
+async fn channel_id(kv: &KvStore, team_id: &str, channel: &str) -> std::result::Result<Option<String>, Box<dyn Error>> {
+    if is_channel_id(channel) {
+        return Ok(Some(channel.to_string()));
+    }
+
+    Store.channel_id(kv, team_id, channel.trim_start_matches('#')).await
+}
+
+fn broadcast_enabled(ctx: &RouteContext<Context>) -> bool {
+    ctx.var("THREAD_BROADCAST").map(|value| value.to_string() == "true").unwrap_or(false)
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::error::Error;
+use worker::*;
+
+use crate::adapter::{
+    github::Github,
+    slack::Slack,
+    store::Store,
+};
+use crate::domain::model::{
+    block_kit::Block,
+    command::Command,
+    github::{CiStatus, GithubLink, IssueReference},
+    slack::{AppHomeOpenedEvent, AppMentionEvent, LinkSharedEvent, MessageEvent, SlackEvent, Unfurl},
+};
+use crate::driver::{
+    delivery::index_card,
+    identity::mention_users,
+    installation::bot_token,
+};
+
+const AUTO_EXPAND_LIMIT: usize = 5;
+
+pub async fn handle_event(team_id: &str, bot_user_id: Option<&str>, event: SlackEvent, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    match event {
+        SlackEvent::Message(event) => expand_references(team_id, &event, bot_user_id, ctx).await,
+        SlackEvent::AppMention(event) => reply_to_mention(team_id, &event, ctx).await,
+        SlackEvent::LinkShared(event) => unfurl_links(team_id, &event, ctx).await,
+        SlackEvent::AppHomeOpened(event) => publish_home(team_id, &event, ctx).await,
+        SlackEvent::AppUninstalled => Store.delete_installation(&ctx.kv("STORE")?, team_id).await,
+        SlackEvent::TokensRevoked(event) if !event.tokens.bot.is_empty() => Store.delete_installation(&ctx.kv("STORE")?, team_id).await,
+        SlackEvent::TokensRevoked(_) => Ok(()),
+        SlackEvent::Unsupported => Ok(()),
+    }
+}
+
+async fn unfurl_links(team_id: &str, event: &LinkSharedEvent, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+
+    let mut unfurls = HashMap::new();
+
+    for link in &event.links {
+        let github_link = match GithubLink::parse(&link.url) {
+            Some(github_link) => github_link,
+            None => continue,
+        };
+
+        // Links that cannot be resolved (deleted, no access, ...) are simply left without a preview.
+        match shared_with(&github_token, github_link.owner(), github_link.repo(), &event.channel, ctx).await {
+            Ok(true) => {},
+            _ => continue,
+        }
+
+        let blocks = match &github_link {
+            GithubLink::Issue(reference) => Github
+                .fetch_issue(&github_token, reference)
+                .await
+                .map(|issue| Slack.construct_message(&issue, &Slack.text_lines(&issue, &reference.to_string(), ""))),
+            GithubLink::PullRequest(reference) => Github
+                .fetch_pull_request(&github_token, reference)
+                .await
+                .map(|pr| Slack.construct_message(&pr, &Slack.text_lines(&pr, &reference.to_string(), ""))),
+            GithubLink::Commit { owner, repo, sha } => Github
+                .fetch_commit(&github_token, owner, repo, sha)
+                .await
+                .map(|commit| Slack.construct_commit_message(&commit, owner, repo)),
+        };
+
+        if let Ok(blocks) = blocks {
+            unfurls.insert(link.url.to_string(), Unfurl { blocks: blocks.into_iter().flat_map(Block::split).collect() });
+        }
+    }
+
+    if unfurls.is_empty() {
+        return Ok(());
+    }
+
+    Slack.unfurl(
+        &bot_token(team_id, &ctx.env).await?,
+        &event.channel,
+        &event.message_ts,
+        unfurls,
+    ).await
+}
+
+/// Whether content of `owner/repo` may be shown in `channel`: private repositories only reach the channels they were shared with.
+pub async fn shared_with(github_token: &str, owner: &str, repo: &str, channel: &str, ctx: &RouteContext<Context>) -> std::result::Result<bool, Box<dyn Error>> {
+    let repository = Github.fetch_repository(github_token, owner, repo).await?;
+
+    Ok(!repository.private || Store.repository_channels(&ctx.kv("STORE")?, &repository.full_name).await?.iter().any(|c| c == channel))
+}
+
+async fn reply_to_mention(team_id: &str, event: &AppMentionEvent, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let bot_token = bot_token(team_id, &ctx.env).await?;
+    let thread_ts = event.thread_ts.as_deref().unwrap_or(&event.ts);
+    let text = event.text_without_mentions();
+
+    let reference = match Command::parse(&text) {
+        Some(Command::Lookup(reference)) => reference,
+        Some(Command::AutoExpand(enabled)) => {
+            Store.set_auto_expand(&ctx.kv("STORE")?, &event.channel, enabled).await?;
+
+            return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), &Slack.auto_expand_text(enabled)).await.map(|_| ());
+        },
+        Some(Command::DirectMessages(enabled)) => {
+            Store.set_direct_messages(&ctx.kv("STORE")?, team_id, &event.user, enabled).await?;
+
+            return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), &Slack.direct_messages_text(enabled)).await.map(|_| ());
+        },
+        // Sign-in links are personal, so they are never posted in a channel.
+        Some(Command::Link(_)) => return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), "Use `/lookup link <github-login>` to link your GitHub account.").await.map(|_| ()),
+        Some(Command::Route(_) | Command::Unroute(_) | Command::Routes) => return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), "Use `/lookup route`, `/lookup unroute` or `/lookup routes` to manage where GitHub events are posted.").await.map(|_| ()),
+        Some(Command::Share(_) | Command::Unshare(_)) => return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), "Use `/lookup share org/repo` or `/lookup unshare org/repo` to show a private repository in this channel.").await.map(|_| ()),
+        Some(Command::Subscribe { .. } | Command::Unsubscribe { .. } | Command::Subscriptions | Command::Filter { .. } | Command::Digest { .. }) => return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), "Use `/lookup subscribe`, `/lookup unsubscribe`, `/lookup filter`, `/lookup digest` or `/lookup subscriptions` to manage what this channel receives.").await.map(|_| ()),
+        Some(Command::QuietHours(_)) => return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), "Use `/lookup quiet 22:00-07:00 Europe/Berlin` or `/lookup quiet off` to set quiet hours for this channel.").await.map(|_| ()),
+        Some(Command::Invalid(text)) => return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), &text).await.map(|_| ()),
+        None => return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), "Issue reference not found").await.map(|_| ()),
+    };
+
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+
+    if !shared_with(&github_token, &reference.owner, &reference.repo, &event.channel, ctx).await.unwrap_or(false) {
+        return Slack.post_text(&bot_token, &event.channel, Some(thread_ts), &format!("Unable to fetch {}", reference)).await.map(|_| ());
+    }
+
+    match Github.fetch_issue(&github_token, &reference).await {
+        Ok(issue) => {
+            let labels = Github.fetch_labels(&github_token, &reference.owner, &reference.repo).await.unwrap_or_default();
+
+            let message = mention_users(team_id, Slack.create_lookup_message(&issue, &text, &reference, &labels), &ctx.env).await?;
+            let posted = Slack.post_message(&bot_token, &event.channel, Some(thread_ts), message).await?;
+            index_card(team_id, &reference, &posted, &ctx.env).await?;
+        },
+        Err(e) => {
+            Slack.post_text(&bot_token, &event.channel, Some(thread_ts), &format!("Unable to fetch {}: {}", reference, e)).await?;
+        },
+    }
+
+    Ok(())
+}
+
+async fn expand_references(team_id: &str, event: &MessageEvent, bot_user_id: Option<&str>, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let text = match &event.text {
+        Some(text) if event.is_user_message() => text,
+        _ => return Ok(()),
+    };
+
+    // Mentions of the bot are answered by the app_mention handler.
+    if bot_user_id.is_some_and(|user_id| text.contains(&format!("<@{}>", user_id))) {
+        return Ok(());
+    }
+
+    let mut references: Vec<IssueReference> = vec![];
+
+    for reference in IssueReference::parse_all(text) {
+        if !references.contains(&reference) {
+            references.push(reference);
+        }
+    }
+
+    references.truncate(AUTO_EXPAND_LIMIT);
+
+    if references.is_empty() || !Store.auto_expand_enabled(&ctx.kv("STORE")?, &event.channel).await? {
+        return Ok(());
+    }
+
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+
+    let mut issues = vec![];
+
+    for reference in references {
+        if !shared_with(&github_token, &reference.owner, &reference.repo, &event.channel, ctx).await.unwrap_or(false) {
+            continue;
+        }
+
+        if let Ok(issue) = Github.fetch_issue(&github_token, &reference).await {
+            issues.push((reference, issue));
+        }
+    }
+
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    Slack.post_text(
+        &bot_token(team_id, &ctx.env).await?,
+        &event.channel,
+        Some(event.thread_ts.as_deref().unwrap_or(&event.ts)),
+        &Slack.reference_summary(&issues),
+    ).await?;
+
+    Ok(())
+}
+
+async fn publish_home(team_id: &str, event: &AppHomeOpenedEvent, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    if event.tab != "home" {
+        return Ok(());
+    }
+
+    let bot_token = bot_token(team_id, &ctx.env).await?;
+
+    let login = match Store.github_login(&ctx.kv("STORE")?, team_id, &event.user).await? {
+        Some(login) => login,
+        None => return Slack.publish_view(&bot_token, &event.user, Slack.construct_home_view(None, &[], &[], &[])).await,
+    };
+
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+
+    let assigned = Github
+        .search_issues(&github_token, &format!("is:open is:issue archived:false assignee:{}", login))
+        .await?;
+    let review_requests = Github
+        .search_issues(&github_token, &format!("is:open is:pr archived:false review-requested:{}", login))
+        .await?;
+    let pull_requests = Github
+        .search_issues(&github_token, &format!("is:open is:pr archived:false author:{}", login))
+        .await?;
+
+    let mut pull_requests_with_status = vec![];
+
+    for item in pull_requests.items {
+        let status = Github.fetch_ci_status(&github_token, &item.reference()).await.unwrap_or(CiStatus::Unknown);
+        pull_requests_with_status.push((item, status));
+    }
+
+    Slack.publish_view(
+        &bot_token,
+        &event.user,
+        Slack.construct_home_view(Some(&login), &assigned.items, &review_requests.items, &pull_requests_with_status),
+    ).await
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::error::Error;
+use worker::*;
+
+use crate::adapter::{
+    github::Github,
+    slack::Slack,
+    store::Store,
+};
+use crate::domain::model::{github::User, mrkdwn::link, slack::Message};
+use crate::driver::installation::bot_token;
+
+/// Renders GitHub users linked to an account in `team_id` as Slack mentions.
+pub async fn mention_users(team_id: &str, message: Message, env: &Env) -> std::result::Result<Message, Box<dyn Error>> {
+    let kv = env.kv("STORE")?;
+    let mut slack_users = HashMap::new();
+
+    for login in message.github_logins() {
+        if let Some(user_id) = Store.slack_user(&kv, team_id, &login).await? {
+            slack_users.insert(login, user_id);
+        }
+    }
+
+    Ok(message.mention_users(&slack_users))
+}
+
+/// Links the user to the GitHub account whose public email matches their Slack email, when `GITHUB_EMAIL_MATCHING` is on.
+pub async fn link_by_email(team_id: &str, user_id: &str, ctx: &RouteContext<Context>) -> std::result::Result<String, Box<dyn Error>> {
+    if !email_matching_enabled(ctx) {
+        return Ok("Usage: `/lookup link <github-login>`".to_string());
+    }
+
+    match match_by_email(team_id, user_id, ctx).await? {
+        Some(github_user) => Ok(format!("Linked to GitHub account {}.", link(&github_user.html_url, &github_user.login))),
+        None => Ok("No GitHub account with your email was found. Use `/lookup link <github-login>` instead.".to_string()),
+    }
+}
+
+/// The GitHub login linked to the user. Users who never linked one are matched by email on first use
+/// when `GITHUB_EMAIL_MATCHING` is on.
+pub async fn github_login(team_id: &str, user_id: &str, ctx: &RouteContext<Context>) -> std::result::Result<Option<String>, Box<dyn Error>> {
+    if let Some(login) = Store.github_login(&ctx.kv("STORE")?, team_id, user_id).await? {
+        return Ok(Some(login));
+    }
+
+    if !email_matching_enabled(ctx) {
+        return Ok(None);
+    }
+
+    Ok(match_by_email(team_id, user_id, ctx).await?.map(|github_user| github_user.login))
+}
+
+async fn match_by_email(team_id: &str, user_id: &str, ctx: &RouteContext<Context>) -> std::result::Result<Option<User>, Box<dyn Error>> {
+    let user = Slack.fetch_user(&bot_token(team_id, &ctx.env).await?, user_id).await?;
+    let email = user.profile.email.ok_or("Your Slack profile has no email address")?;
+
+    let mut candidates = Github.search_users_by_email(&ctx.secret("GITHUB_TOKEN")?.to_string(), &email).await?;
+
+    // Anything but a single match is too ambiguous to link without the user signing in.
+    match (candidates.pop(), candidates.is_empty()) {
+        (Some(github_user), true) => {
+            Store.link_user(&ctx.kv("STORE")?, team_id, user_id, &github_user.login).await?;
+
+            Ok(Some(github_user))
+        },
+        _ => Ok(None),
+    }
+}
+
+fn email_matching_enabled(ctx: &RouteContext<Context>) -> bool {
+    ctx.var("GITHUB_EMAIL_MATCHING").map(|value| value.to_string() == "true").unwrap_or(false)
+}
@@ -0,0 +1,30 @@
+use std::error::Error;
+use worker::*;
+
+use crate::adapter::store::Store;
+
+pub async fn bot_token(team_id: &str, env: &Env) -> std::result::Result<String, Box<dyn Error>> {
+    if let Some(installation) = Store.installation(&env.kv("STORE")?, team_id).await? {
+        return Ok(installation.bot_token);
+    }
+
+    // Single-workspace deployments configured before OAuth installs keep working, for their own workspace only.
+    let configured = env.var("SLACK_TEAM_ID").map(|value| value.to_string()).unwrap_or_default();
+
+    match env.secret("SLACK_BOT_TOKEN") {
+        Ok(bot_token) if !team_id.is_empty() && team_id == configured => Ok(bot_token.to_string()),
+        _ => Err(format!("The app is not installed to {}", team_id).into()),
+    }
+}
+
+/// Every workspace the bot can post to: OAuth installs, and the `SLACK_TEAM_ID` workspace when a `SLACK_BOT_TOKEN` is configured.
+pub async fn workspaces(env: &Env) -> std::result::Result<Vec<String>, Box<dyn Error>> {
+    let mut teams = Store.installed_teams(&env.kv("STORE")?).await?;
+    let configured = env.var("SLACK_TEAM_ID").map(|value| value.to_string()).unwrap_or_default();
+
+    if !configured.is_empty() && env.secret("SLACK_BOT_TOKEN").is_ok() && !teams.contains(&configured) {
+        teams.push(configured);
+    }
+
+    Ok(teams)
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::error::Error;
+use worker::*;
+
+use crate::adapter::{
+    github::Github,
+    slack::Slack,
+    store::Store,
+};
+use crate::domain::model::{
+    github::{IssueReference, NewIssue, Repository},
+    slack::{
+        BlockAction, BlockActionsPayload, CardAction, InteractionContainer, InteractionPayload, InteractionTeam, Message, MessageActionPayload,
+        ResponseUrlMessage, ShortcutOrigin, ViewPayload, ViewSubmissionPayload, ViewSubmissionResponse,
+    },
+};
+use crate::driver::{
+    delivery::index_card,
+    events::shared_with,
+    identity::{github_login, mention_users},
+    installation::bot_token,
+};
+
+pub async fn handle_interaction(payload: InteractionPayload, ctx: &RouteContext<Context>) -> std::result::Result<Option<ViewSubmissionResponse>, Box<dyn Error>> {
+    match payload {
+        InteractionPayload::BlockActions(payload) => handle_block_actions(&payload, ctx).await.map(|_| None),
+        InteractionPayload::MessageAction(payload) => handle_message_action(&payload, ctx).await.map(|_| None),
+        InteractionPayload::ViewSubmission(payload) => handle_view_submission(&payload, ctx).await,
+        InteractionPayload::Unsupported => Ok(None),
+    }
+}
+
+async fn handle_block_actions(payload: &BlockActionsPayload, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    if let Some(view) = &payload.view {
+        return match view.callback_id.as_str() {
+            "create_issue" => update_create_issue_view(payload, view, ctx).await,
+            _ => Ok(()),
+        };
+    }
+
+    let response_url = match &payload.response_url {
+        Some(response_url) => response_url,
+        None => return Ok(()),
+    };
+    let card = payload.container.as_ref().and_then(InteractionContainer::message);
+
+    for action in payload.actions.iter().filter_map(BlockAction::card_action) {
+        let team_id = team_id(payload.team.as_ref());
+
+        // Failures are reported only to the clicking user, the card itself stays as it is.
+        let message = match perform_card_action(&action, team_id, &payload.user.id, ctx).await {
+            Ok(message) => {
+                // response_url expires after half an hour, so cards posted by the bot are edited in place instead.
+                if let Some(card) = &card {
+                    if Slack.update_message(&bot_token(team_id, &ctx.env).await?, card, message.clone()).await.is_ok() {
+                        continue;
+                    }
+                }
+
+                let page = message.pages().into_iter().next().ok_or("message has no content")?;
+
+                ResponseUrlMessage {
+                    replace_original: true,
+                    response_type: None,
+                    blocks: page.blocks,
+                    attachments: page.attachments,
+                    text: Some(page.text),
+                }
+            },
+            Err(e) => ResponseUrlMessage {
+                replace_original: false,
+                response_type: Some("ephemeral".to_string()),
+                blocks: vec![],
+                attachments: vec![],
+                text: Some(e.to_string()),
+            },
+        };
+
+        Slack.respond(response_url, &message).await?;
+    }
+
+    Ok(())
+}
+
+async fn perform_card_action(action: &CardAction, team_id: &str, user_id: &str, ctx: &RouteContext<Context>) -> std::result::Result<Message, Box<dyn Error>> {
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+    let reference = action.reference();
+
+    let login = github_login(team_id, user_id, ctx)
+        .await?
+        .ok_or("Link your GitHub account with `/lookup link <github-login>` to use these buttons")?;
+
+    let permission = Github.fetch_permission(&github_token, &reference.owner, &reference.repo, &login).await?;
+
+    if !permission.can_write() {
+        return Err(format!("{} does not have write access to {}/{}", login, reference.owner, reference.repo).into());
+    }
+
+    match action {
+        CardAction::Close(reference) => {
+            Github.update_issue_state(&github_token, reference, "closed").await?;
+        },
+        CardAction::Reopen(reference) => {
+            Github.update_issue_state(&github_token, reference, "open").await?;
+        },
+        CardAction::AssignToMe(reference) => {
+            Github.add_assignees(&github_token, reference, &[&login]).await?;
+        },
+        CardAction::AddLabel(reference, label) => {
+            Github.add_labels(&github_token, reference, &[label]).await?;
+        },
+    }
+
+    let issue = Github.fetch_issue(&github_token, reference).await?;
+    let labels = Github.fetch_labels(&github_token, &reference.owner, &reference.repo).await.unwrap_or_default();
+
+    mention_users(team_id, Slack.create_lookup_message(&issue, &reference.to_string(), reference, &labels), &ctx.env).await
+}
+
+async fn handle_message_action(payload: &MessageActionPayload, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    if payload.callback_id != "create_issue" {
+        return Ok(());
+    }
+
+    let team_id = team_id(payload.team.as_ref());
+    let bot_token = bot_token(team_id, &ctx.env).await?;
+    let loading = Slack.open_view(&bot_token, &payload.trigger_id, Slack.construct_notice_view("Loading repositories…")).await?;
+
+    if github_login(team_id, &payload.user.id, ctx).await?.is_none() {
+        let notice = Slack.construct_notice_view("Link your GitHub account with `/lookup link <github-login>` to create issues.");
+
+        return Slack.update_view(&bot_token, &loading.id, &loading.hash, notice).await;
+    }
+
+    let message = &payload.message;
+
+    let permalink = Slack.fetch_permalink(&bot_token, &payload.channel.id, &message.ts).await?;
+
+    let author = match &message.user {
+        Some(user) => Slack.fetch_user(&bot_token, user).await.ok(),
+        None => None,
+    };
+
+    let (title, body) = Slack.issue_draft(&message.text, author.as_ref(), &permalink);
+    let repositories = visible_repositories(&payload.channel.id, ctx).await?;
+
+    let origin = ShortcutOrigin {
+        channel: payload.channel.id.to_string(),
+        thread_ts: message.thread_ts.as_deref().unwrap_or(&message.ts).to_string(),
+    };
+
+    Slack.update_view(
+        &bot_token,
+        &loading.id,
+        &loading.hash,
+        Slack.construct_create_issue_view(&title, &body, &repositories, &[], &origin),
+    ).await
+}
+
+// Labels depend on the repository, so the modal is rebuilt once one is selected.
+async fn update_create_issue_view(payload: &BlockActionsPayload, view: &ViewPayload, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let full_name = match payload.actions.iter().find(|action| action.action_id == "select_repository") {
+        Some(BlockAction { selected_option: Some(option), .. }) => &option.value,
+        _ => return Ok(()),
+    };
+
+    let (owner, repo) = full_name.split_once('/').ok_or("Invalid repository")?;
+
+    let origin: ShortcutOrigin = serde_json::from_str(&view.private_metadata)?;
+    let repositories = visible_repositories(&origin.channel, ctx).await?;
+
+    if !repositories.iter().any(|repository| &repository.full_name == full_name) {
+        return Err(format!("{} is not offered in this channel", full_name).into());
+    }
+
+    let labels = Github.fetch_labels(&ctx.secret("GITHUB_TOKEN")?.to_string(), owner, repo).await.unwrap_or_default();
+
+    Slack.update_view(
+        &bot_token(team_id(payload.team.as_ref()), &ctx.env).await?,
+        &view.id,
+        &view.hash,
+        Slack.construct_create_issue_view(
+            view.state.text("title").unwrap_or(""),
+            view.state.text("body").unwrap_or(""),
+            &repositories,
+            &labels,
+            &origin,
+        ),
+    ).await
+}
+
+/// Only checks the submission before answering, Slack gives up on the modal after 3 seconds.
+/// The issue is created and posted once the modal is closed.
+async fn handle_view_submission(payload: &ViewSubmissionPayload, ctx: &RouteContext<Context>) -> std::result::Result<Option<ViewSubmissionResponse>, Box<dyn Error>> {
+    if payload.view.callback_id != "create_issue" {
+        return Ok(None);
+    }
+
+    let state = &payload.view.state;
+
+    let (owner, repo) = match state.selected("repository").and_then(|full_name| full_name.split_once('/')) {
+        Some(repository) => repository,
+        None => return Ok(Some(view_errors("repository", "Select a repository"))),
+    };
+
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+    let team_id = team_id(payload.team.as_ref());
+    let origin: ShortcutOrigin = serde_json::from_str(&payload.view.private_metadata)?;
+
+    let login = match github_login(team_id, &payload.user.id, ctx).await? {
+        Some(login) => login,
+        None => return Ok(Some(view_errors("repository", "Link your GitHub account with `/lookup link <github-login>` to create issues"))),
+    };
+
+    if !shared_with(&github_token, owner, repo, &origin.channel, ctx).await.unwrap_or(false) {
+        return Ok(Some(view_errors("repository", &format!("{}/{} is private and not shared with this channel", owner, repo))));
+    }
+
+    if !Github.fetch_permission(&github_token, owner, repo, &login).await?.can_write() {
+        return Ok(Some(view_errors("repository", &format!("{} does not have write access to {}/{}", login, owner, repo))));
+    }
+
+    let issue = NewIssue {
+        title: state.text("title").unwrap_or("").to_string(),
+        body: state.text("body").unwrap_or("").to_string(),
+        labels: state.selected_all("labels").into_iter().map(str::to_string).collect(),
+    };
+
+    let (team_id, user_id, owner, repo, env) = (team_id.to_string(), payload.user.id.to_string(), owner.to_string(), repo.to_string(), Env::from(ctx.env.clone()));
+
+    ctx.data.wait_until(async move {
+        if let Err(e) = create_issue(&team_id, &owner, &repo, &issue, &origin, &env).await {
+            let text = format!("Unable to create issue in {}/{}: {}", owner, repo, e);
+
+            if let Err(e) = notify_user(&team_id, &user_id, &text, &env).await {
+                console_error!("{} ({})", text, e);
+            }
+        }
+    });
+
+    Ok(None)
+}
+
+/// Creates the issue and posts its card into the thread the shortcut was used in.
+async fn create_issue(team_id: &str, owner: &str, repo: &str, issue: &NewIssue, origin: &ShortcutOrigin, env: &Env) -> std::result::Result<(), Box<dyn Error>> {
+    let github_token = env.secret("GITHUB_TOKEN")?.to_string();
+    let issue = Github.create_issue(&github_token, owner, repo, issue).await?;
+
+    let reference = IssueReference {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        number: issue.number,
+    };
+
+    let labels = Github.fetch_labels(&github_token, owner, repo).await.unwrap_or_default();
+
+    let posted = Slack.post_message(
+        &bot_token(team_id, env).await?,
+        &origin.channel,
+        Some(&origin.thread_ts),
+        mention_users(team_id, Slack.create_lookup_message(&issue, &reference.to_string(), &reference, &labels), env).await?,
+    ).await?;
+
+    index_card(team_id, &reference, &posted, env).await
+}
+
+async fn notify_user(team_id: &str, user_id: &str, text: &str, env: &Env) -> std::result::Result<(), Box<dyn Error>> {
+    let bot_token = bot_token(team_id, env).await?;
+    let channel = Slack.open_conversation(&bot_token, user_id).await?;
+
+    Slack.post_text(&bot_token, &channel, None, text).await.map(|_| ())
+}
+
+/// Repositories issues may be created in from `channel`: public ones, and private ones shared with it.
+async fn visible_repositories(channel: &str, ctx: &RouteContext<Context>) -> std::result::Result<Vec<Repository>, Box<dyn Error>> {
+    let kv = ctx.kv("STORE")?;
+    let mut visible = vec![];
+
+    for repository in Github.fetch_repositories(&ctx.secret("GITHUB_TOKEN")?.to_string()).await? {
+        if !repository.private || Store.repository_channels(&kv, &repository.full_name).await?.iter().any(|c| c == channel) {
+            visible.push(repository);
+        }
+    }
+
+    Ok(visible)
+}
+
+fn team_id(team: Option<&InteractionTeam>) -> &str {
+    team.map(|team| team.id.as_str()).unwrap_or_default()
+}
+
+fn view_errors(block_id: &str, message: &str) -> ViewSubmissionResponse {
+    ViewSubmissionResponse {
+        response_action: "errors".to_string(),
+        errors: HashMap::from([(block_id.to_string(), message.to_string())]),
+    }
+}
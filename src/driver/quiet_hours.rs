@@ -0,0 +1,67 @@
+use std::error::Error;
+use chrono::DateTime;
+use worker::*;
+
+use crate::adapter::{
+    slack::Slack,
+    store::Store,
+};
+use crate::domain::model::{
+    quiet_hours::{is_urgent, QuietHours},
+    rollup::Queue,
+    routes::GithubWebhookRequest,
+    slack::MessageSink,
+};
+
+pub async fn set_quiet_hours(team_id: &str, channel: &str, quiet_hours: Option<QuietHours>, ctx: &RouteContext<Context>) -> std::result::Result<String, Box<dyn Error>> {
+    Store.set_quiet_hours(&ctx.kv("STORE")?, team_id, channel, quiet_hours.as_ref()).await?;
+
+    Ok(Slack.quiet_hours_text(quiet_hours.as_ref()))
+}
+
+/// Incoming webhooks belong to no channel the bot knows, so they are never quiet.
+pub async fn is_quiet(sink: &MessageSink, env: &Env) -> std::result::Result<bool, Box<dyn Error>> {
+    let (team_id, channel) = match sink {
+        MessageSink::Channel { team_id, channel } => (team_id, channel),
+        MessageSink::IncomingWebhook(_) => return Ok(false),
+    };
+
+    let now = DateTime::from_timestamp_millis(Date::now().as_millis() as i64).unwrap_or_default();
+
+    Ok(Store
+        .quiet_hours(&env.kv("STORE")?, team_id, channel)
+        .await?
+        .is_some_and(|quiet_hours| quiet_hours.is_quiet(now)))
+}
+
+/// Queues the event for the catch-up of sinks in their quiet hours and returns the others.
+/// Events labelled with one of `URGENT_LABELS` go everywhere right away.
+pub async fn hold_quiet(body: &GithubWebhookRequest, sinks: Vec<MessageSink>, ctx: &RouteContext<Context>) -> std::result::Result<Vec<MessageSink>, Box<dyn Error>> {
+    let urgent_labels: Vec<String> = ctx
+        .var("URGENT_LABELS")
+        .map(|value| value.to_string())
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let entry = match body.rollup_entry() {
+        Some(entry) if !is_urgent(&body.filter_event().labels, &urgent_labels) => entry,
+        _ => return Ok(sinks),
+    };
+
+    let kv = ctx.kv("STORE")?;
+    let mut live = vec![];
+
+    for sink in sinks {
+        if is_quiet(&sink, &ctx.env).await? {
+            Store.enqueue(&kv, Queue::CatchUp, &sink, entry.clone()).await?;
+        } else {
+            live.push(sink);
+        }
+    }
+
+    Ok(live)
+}
@@ -0,0 +1,82 @@
+use std::error::Error;
+use worker::*;
+
+use crate::adapter::{
+    slack::Slack,
+    store::Store,
+};
+use crate::domain::model::{
+    digest::Frequency,
+    rollup::{BotPolicy, Queue},
+    routes::GithubWebhookRequest,
+    slack::MessageSink,
+};
+use crate::driver::{
+    delivery::deliver,
+    quiet_hours::is_quiet,
+};
+
+/// `BOT_EVENTS` only applies to activity of bot accounts: `[bot]` logins and those in `BOT_ACCOUNTS`.
+pub fn bot_policy(body: &GithubWebhookRequest, ctx: &RouteContext<Context>) -> BotPolicy {
+    let var = |name: &str| ctx.var(name).map(|value| value.to_string()).unwrap_or_default();
+    let accounts: Vec<String> = var("BOT_ACCOUNTS")
+        .split(',')
+        .map(str::trim)
+        .filter(|login| !login.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    match body.is_bot_activity(&accounts) {
+        true => BotPolicy::parse(&var("BOT_EVENTS")),
+        false => BotPolicy::Deliver,
+    }
+}
+
+/// Keeps the event for the next rollup of every sink it would have gone to.
+pub async fn collect(body: &GithubWebhookRequest, sinks: &[MessageSink], ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let entry = match body.rollup_entry() {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    let kv = ctx.kv("STORE")?;
+
+    for sink in sinks {
+        Store.enqueue(&kv, Queue::Rollup, sink, entry.clone()).await?;
+    }
+
+    Ok(())
+}
+
+/// Records the event for the next digest of every channel collecting one.
+pub async fn collect_digests(body: &GithubWebhookRequest, digests: &[(MessageSink, Frequency)], ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    let entry = match body.rollup_entry() {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    let kv = ctx.kv("STORE")?;
+
+    for (sink, frequency) in digests {
+        Store.enqueue(&kv, Queue::Digest(*frequency), sink, entry.clone()).await?;
+    }
+
+    Ok(())
+}
+
+/// Posts and clears what waits in `queue` for channels outside their quiet hours. Digests are
+/// only tried once they are due, so they go out whether or not the channel is quiet. A sink that
+/// cannot be reached keeps its events for the next run rather than holding back the others.
+pub async fn post_queued(queue: Queue, env: &Env) -> std::result::Result<(), Box<dyn Error>> {
+    let kv = env.kv("STORE")?;
+
+    for rollup in Store.queued(&kv, queue).await? {
+        if !matches!(queue, Queue::Digest(_)) && is_quiet(&rollup.sink, env).await? {
+            continue;
+        }
+
+        if deliver(&rollup.sink, Slack.create_rollup_message(&rollup, queue), env).await.is_ok() {
+            Store.dequeue(&kv, &rollup.keys).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,78 +1,653 @@
+use std::error::Error;
+use chrono::DateTime;
 use worker::*;
 
 use crate::adapter::{
     github::Github,
     slack::Slack,
+    store::Store,
 };
 use crate::domain::model::{
+    command::Command,
+    digest::Frequency,
+    github::IssueReference,
+    quiet_hours::QuietHours,
+    rollup::{BotPolicy, Queue},
+    routing::RouteScope,
     routes::{
+        AlertWebhookRequest,
         SlashCommandRequest,
         GithubWebhookRequest,
+        InteractionRequest,
+        Release,
+        SlackEventRequest,
     },
-    slack::Message,
+    slack::{Installation, InteractionPayload, MessageSink},
+};
+use crate::driver::{
+    delivery::{delete_cards, notification_sinks, publish, publish_alert, publish_release, refresh_cards, send_direct_messages},
+    events::{handle_event, shared_with},
+    identity::{link_by_email, mention_users},
+    installation::bot_token,
+    interactions::handle_interaction,
+    quiet_hours::{hold_quiet, set_quiet_hours},
+    rollups::{bot_policy, collect, collect_digests, post_queued},
+    subscriptions::manage_subscriptions,
 };
 
-pub async fn lookup(mut req: Request, _ctx: RouteContext<()>) -> Result<Response> {
+pub async fn lookup(mut req: Request, ctx: RouteContext<Context>) -> Result<Response> {
     let body = req.text().await?;
 
     if body.is_empty() {
         return Response::error("Not found", 404);
     }
 
+    // Commands act for the user who sent them, so only requests signed by Slack are trusted with any of them.
+    if !verify_slack_request(&req, &body, &ctx)? {
+        return Response::error("Invalid signature", 401);
+    }
+
     let params: SlashCommandRequest = serde_qs::from_str(&body).unwrap();
 
+    match Command::parse(&params.text) {
+        Some(Command::Lookup(reference)) => lookup_issue(&body, &params, &reference, &ctx).await,
+        Some(Command::Link(login)) => link_account(&req, &params, login, &ctx).await,
+        Some(command @ (Command::Route(_) | Command::Unroute(_) | Command::Routes)) => manage_routes(&params, command, &ctx).await,
+        Some(command @ (Command::Share(_) | Command::Unshare(_))) => share(&params, command, &ctx).await,
+        Some(command @ (Command::Subscribe { .. } | Command::Unsubscribe { .. } | Command::Subscriptions | Command::Filter { .. } | Command::Digest { .. })) => subscribe(&params, command, &ctx).await,
+        Some(Command::QuietHours(quiet_hours)) => quiet(&params, quiet_hours, &ctx).await,
+        Some(Command::DirectMessages(enabled)) => direct_messages(&params, enabled, &ctx).await,
+        Some(Command::AutoExpand(enabled)) => auto_expand(&params, enabled, &ctx).await,
+        Some(Command::Invalid(text)) => Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": text,
+        })),
+        None => Response::error("Issue reference not found", 400),
+    }
+}
+
+async fn lookup_issue(body: &str, params: &SlashCommandRequest, reference: &IssueReference, ctx: &RouteContext<Context>) -> Result<Response> {
+    let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+
+    let shared = shared_with(&github_token, &reference.owner, &reference.repo, &params.channel_id, ctx)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    match shared {
+        Ok(true) => {},
+        Ok(false) => return Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": format!("{} is private and not shared with this channel", reference),
+        })),
+        Err(e) => return Response::error(format!("{:?}", e), 500),
+    }
+
     let issue = Github
-        .fetch_issue(&params.text)
+        .fetch_issue(&github_token, reference)
         .await
         .map_err(|e| worker::Error::RustError(format!("{}", e)));
 
     match issue {
         Ok(issue) => {
-            let result = serde_json::json!(
-                Message {
-                    blocks: Slack.construct_message(
-                        &issue,
-                        &Slack.text_lines(&issue, &body, "")
-                    ),
-                    response_type: "in_channel".to_string(),
-                }
+            let labels = Github.fetch_labels(&github_token, &reference.owner, &reference.repo).await.unwrap_or_default();
+            let message = Slack.create_lookup_message(&issue, body, reference, &labels);
+            let message = mention_users(&params.team_id, message, &ctx.env).await.map_err(|e| worker::Error::RustError(format!("{}", e)))?;
+
+            let page = message.pages().into_iter().next().ok_or_else(|| worker::Error::RustError("message has no content".to_string()))?;
+
+            Response::from_json(&page)
+        },
+        Err(e) => Response::error(format!("{:?}", e), 500),
+    }
+}
+
+async fn link_account(req: &Request, params: &SlashCommandRequest, login: Option<String>, ctx: &RouteContext<Context>) -> Result<Response> {
+    let text = match login {
+        Some(login) => {
+            let state = Slack.link_state(
+                &ctx.secret("GITHUB_CLIENT_SECRET")?.to_string(),
+                &params.team_id,
+                &params.user_id,
+                &login,
+                Date::now().as_millis() / 1000,
             );
 
-            Response::from_json(&result)
+            let authorize_url = Url::parse_with_params(
+                "https://github.com/login/oauth/authorize",
+                &[
+                    ("client_id", ctx.var("GITHUB_CLIENT_ID")?.to_string()),
+                    ("redirect_uri", github_redirect_uri(req)?),
+                    ("state", state),
+                    ("login", login),
+                ],
+            )?;
+
+            Slack.link_text(authorize_url.as_str())
         },
+        None => link_by_email(&params.team_id, &params.user_id, ctx)
+            .await
+            .unwrap_or_else(|e| format!("Unable to link your account: {}", e)),
+    };
+
+    Response::from_json(&serde_json::json!({
+        "response_type": "ephemeral",
+        "text": text,
+    }))
+}
+
+async fn manage_routes(params: &SlashCommandRequest, command: Command, ctx: &RouteContext<Context>) -> Result<Response> {
+    let result = edit_routing_table(params, command, ctx)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    match result {
+        Ok(text) => Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": text,
+        })),
+        Err(e) => Response::error(format!("{:?}", e), 500),
+    }
+}
+
+async fn subscribe(params: &SlashCommandRequest, command: Command, ctx: &RouteContext<Context>) -> Result<Response> {
+    let result = manage_subscriptions(&params.team_id, &params.channel_id, command, ctx)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    match result {
+        Ok(text) => Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": text,
+        })),
+        Err(e) => Response::error(format!("{:?}", e), 500),
+    }
+}
+
+async fn quiet(params: &SlashCommandRequest, quiet_hours: Option<QuietHours>, ctx: &RouteContext<Context>) -> Result<Response> {
+    let result = set_quiet_hours(&params.team_id, &params.channel_id, quiet_hours, ctx)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    match result {
+        Ok(text) => Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": text,
+        })),
         Err(e) => Response::error(format!("{:?}", e), 500),
     }
 }
 
-pub async fn webhook(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    let body: GithubWebhookRequest = req.json().await?;
+async fn direct_messages(params: &SlashCommandRequest, enabled: bool, ctx: &RouteContext<Context>) -> Result<Response> {
+    let result = Store
+        .set_direct_messages(&ctx.kv("STORE")?, &params.team_id, &params.user_id, enabled)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
 
-    let message = match (&body.issue, &body.pull_request) {
-        (Some(issue), None) => Slack.create_message(issue, &body),
-        (None, Some(pr)) => Slack.create_message(pr, &body),
-        _ => return Response::error("Unknown payload", 400),
+    match result {
+        Ok(_) => Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": Slack.direct_messages_text(enabled),
+        })),
+        Err(e) => Response::error(format!("{:?}", e), 500),
+    }
+}
+
+async fn auto_expand(params: &SlashCommandRequest, enabled: bool, ctx: &RouteContext<Context>) -> Result<Response> {
+    let result = Store
+        .set_auto_expand(&ctx.kv("STORE")?, &params.channel_id, enabled)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    match result {
+        Ok(_) => Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": Slack.auto_expand_text(enabled),
+        })),
+        Err(e) => Response::error(format!("{:?}", e), 500),
+    }
+}
+
+async fn edit_routing_table(params: &SlashCommandRequest, command: Command, ctx: &RouteContext<Context>) -> std::result::Result<String, Box<dyn Error>> {
+    let kv = ctx.kv("STORE")?;
+    let mut table = Store.routing_table(&kv, &params.team_id).await?;
+
+    let scope = match &command {
+        Command::Route(scope) | Command::Unroute(scope) => scope,
+        _ => return Ok(Slack.routing_table_text(&table)),
     };
 
-    let result = Slack.send_message(
-            &ctx.secret("SLACK_WEBHOOK_URL")?.to_string(),
-            &message
-        )
+    // The default route gets every workspace's unrouted events, private repositories included.
+    match scope {
+        RouteScope::Default => {
+            if !is_workspace_admin(params, ctx).await? {
+                return Ok("Only workspace admins can change the default route".to_string());
+            }
+        },
+        RouteScope::Repository { repository, .. } => {
+            let (owner, repo) = repository.split_once('/').unwrap_or_default();
+
+            if matches!(command, Command::Route(_)) && !shared_with(&ctx.secret("GITHUB_TOKEN")?.to_string(), owner, repo, &params.channel_id, ctx).await.unwrap_or(false) {
+                return Ok(format!("{} is private and not shared with this channel, or not visible to the bot's GitHub account", repository));
+            }
+        },
+    }
+
+    let text = match command {
+        Command::Route(scope) => {
+            table.add(&scope, &params.channel_id);
+            Slack.route_text(&scope, true)
+        },
+        Command::Unroute(scope) if table.remove(&scope, &params.channel_id) => Slack.route_text(&scope, false),
+        Command::Unroute(scope) => return Ok(Slack.not_routed_text(&scope)),
+        _ => return Ok(Slack.routing_table_text(&table)),
+    };
+
+    Store.save_routing_table(&kv, &params.team_id, &table).await?;
+
+    Ok(text)
+}
+
+async fn share(params: &SlashCommandRequest, command: Command, ctx: &RouteContext<Context>) -> Result<Response> {
+    let result = edit_repository_channels(params, command, ctx)
         .await
         .map_err(|e| worker::Error::RustError(format!("{}", e)));
 
+    match result {
+        Ok(text) => Response::from_json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": text,
+        })),
+        Err(e) => Response::error(format!("{:?}", e), 500),
+    }
+}
+
+async fn edit_repository_channels(params: &SlashCommandRequest, command: Command, ctx: &RouteContext<Context>) -> std::result::Result<String, Box<dyn Error>> {
+    let (repository, shared) = match &command {
+        Command::Share(repository) => (repository, true),
+        Command::Unshare(repository) => (repository, false),
+        _ => return Err("Not a share command".into()),
+    };
+
+    // Sharing exposes private content to everyone in the channel.
+    if !is_workspace_admin(params, ctx).await? {
+        return Ok("Only workspace admins can share private repositories".to_string());
+    }
+
+    let (owner, repo) = repository.split_once('/').unwrap_or_default();
+
+    // Channels are stored under the name GitHub reports, which is what later lookups compare against.
+    let full_name = match Github.fetch_repository(&ctx.secret("GITHUB_TOKEN")?.to_string(), owner, repo).await {
+        Ok(repository) => repository.full_name,
+        Err(_) => return Ok(format!("{} is not visible to the bot's GitHub account", repository)),
+    };
+
+    let kv = ctx.kv("STORE")?;
+    let mut channels = Store.repository_channels(&kv, &full_name).await?;
+    let listed = channels.contains(&params.channel_id);
+
+    match (shared, listed) {
+        (true, false) => channels.push(params.channel_id.clone()),
+        (false, true) => channels.retain(|c| c != &params.channel_id),
+        _ => return Ok(Slack.share_text(&full_name, shared)),
+    }
+
+    Store.save_repository_channels(&kv, &full_name, &channels).await?;
+
+    Ok(Slack.share_text(&full_name, shared))
+}
+
+async fn is_workspace_admin(params: &SlashCommandRequest, ctx: &RouteContext<Context>) -> std::result::Result<bool, Box<dyn Error>> {
+    let user = Slack.fetch_user(&bot_token(&params.team_id, &ctx.env).await?, &params.user_id).await?;
+
+    Ok(user.is_admin || user.is_owner)
+}
+
+pub async fn webhook(mut req: Request, ctx: RouteContext<Context>) -> Result<Response> {
+    let payload = req.text().await?;
+    let signature = req.headers().get("X-Hub-Signature-256")?.unwrap_or_default();
+
+    if !Github.verify_signature(&ctx.secret("GITHUB_WEBHOOK_SECRET")?.to_string(), &payload, &signature) {
+        return Response::error("Invalid signature", 401);
+    }
+
+    let event_name = req.headers().get("X-GitHub-Event")?;
+
+    if let Some("security_advisory" | "deployment_status") = event_name.as_deref() {
+        let alert: AlertWebhookRequest = serde_json::from_str(&payload)?;
+
+        if !alert.is_urgent() {
+            return Response::ok("OK");
+        }
+
+        return match publish_alert(&alert, &ctx).await {
+            Ok(_) => Response::ok("OK"),
+            Err(e) => Response::error(format!("Unable to handle webhook, message:{:?}", e), 500),
+        };
+    }
+
+    let mut body: GithubWebhookRequest = serde_json::from_str(&payload)?;
+    body.event_name = event_name;
+
+    if let Some(release) = &body.release {
+        let result = announce_release(&body, release, &ctx)
+            .await
+            .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+        return match result {
+            Ok(_) => Response::ok("OK"),
+            Err(e) => Response::error(format!("Unable to handle webhook, message:{:?}", e), 500),
+        };
+    }
+
+    let reference = match body.reference() {
+        Some(reference) => reference,
+        None => return Response::error("Unknown payload", 400),
+    };
+
+    if body.is_item_deleted() {
+        return match delete_cards(&reference, &ctx).await {
+            Ok(_) => Response::ok("OK"),
+            Err(e) => Response::error(format!("Unable to handle webhook, message:{:?}", e), 500),
+        };
+    }
+
+    let policy = bot_policy(&body, &ctx);
+    let result = notify_channels(&body, &reference, policy, &ctx).await;
+    // Direct messages are personal, so neither routes, filters, quiet hours nor digests of a channel hold them back.
+    let result = match policy {
+        BotPolicy::Deliver => send_direct_messages(&body, &reference, &ctx).await.and(result),
+        _ => result,
+    }
+    .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
     match result {
         Ok(_) => Response::ok("OK"),
         Err(e) => Response::error(format!("Unable to handle webhook, message:{:?}", e), 500),
     }
 }
 
+async fn notify_channels(body: &GithubWebhookRequest, reference: &IssueReference, policy: BotPolicy, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    // Subscription filters run before any message is built for the event.
+    let (sinks, digests) = notification_sinks(body, ctx).await?;
+
+    let digested = match policy {
+        BotPolicy::Deliver => collect_digests(body, &digests, ctx).await,
+        _ => Ok(()),
+    };
+    let sinks = match policy {
+        BotPolicy::Deliver => hold_quiet(body, sinks, ctx).await?,
+        _ => sinks,
+    };
+
+    match sinks.is_empty() || policy != BotPolicy::Deliver {
+        true => withhold(body, reference, &sinks, policy, ctx).await,
+        false => publish(body, reference, &sinks, ctx).await,
+    }
+    .and(digested)
+}
+
+/// Drafts and prereleases also send `created` and `released`; only `published` is announced.
+async fn announce_release(body: &GithubWebhookRequest, release: &Release, ctx: &RouteContext<Context>) -> std::result::Result<(), Box<dyn Error>> {
+    if body.action != "published" {
+        return Ok(());
+    }
+
+    let (sinks, digests) = notification_sinks(body, ctx).await?;
+    collect_digests(body, &digests, ctx).await?;
+    let sinks = hold_quiet(body, sinks, ctx).await?;
+
+    publish_release(body, release, &sinks, ctx).await
+}
+
+/// Events filtered out everywhere, left to bots or held for quiet channels are not announced,
+/// but cards already posted still follow the item.
+async fn withhold(
+    body: &GithubWebhookRequest,
+    reference: &IssueReference,
+    sinks: &[MessageSink],
+    policy: BotPolicy,
+    ctx: &RouteContext<Context>,
+) -> std::result::Result<(), Box<dyn Error>> {
+    if policy == BotPolicy::Rollup {
+        collect(body, sinks, ctx).await?;
+    }
+
+    refresh_cards(body, reference, ctx).await
+}
+
+/// Bot rollups, the catch-ups of channels whose quiet hours ended, and digests once due at
+/// `DIGEST_HOUR` UTC.
+pub async fn scheduled(env: &Env) -> std::result::Result<(), Box<dyn Error>> {
+    post_queued(Queue::Rollup, env).await?;
+    post_queued(Queue::CatchUp, env).await?;
+
+    let now = DateTime::from_timestamp_millis(Date::now().as_millis() as i64).unwrap_or_default();
+    let hour = env.var("DIGEST_HOUR").ok().and_then(|value| value.to_string().parse().ok()).unwrap_or(9);
+
+    for frequency in [Frequency::Daily, Frequency::Weekly] {
+        if frequency.is_due(now, hour) {
+            post_queued(Queue::Digest(frequency), env).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn events(mut req: Request, ctx: RouteContext<Context>) -> Result<Response> {
+    let body = req.text().await?;
+
+    if !verify_slack_request(&req, &body, &ctx)? {
+        return Response::error("Invalid signature", 401);
+    }
+
+    // Slack retries deliveries it considers timed out; the first attempt is still being handled.
+    if req.headers().has("X-Slack-Retry-Num")? {
+        return Response::ok("OK");
+    }
+
+    let params: SlackEventRequest = serde_json::from_str(&body)?;
+
+    match params {
+        SlackEventRequest::UrlVerification { challenge } => {
+            Response::from_json(&serde_json::json!({ "challenge": challenge }))
+        },
+        SlackEventRequest::EventCallback { team_id, authorizations, event } => {
+            let bot_user_id = authorizations
+                .iter()
+                .find(|authorization| authorization.is_bot)
+                .map(|authorization| authorization.user_id.as_str());
+
+            match handle_event(&team_id, bot_user_id, event, &ctx).await {
+                Ok(_) => Response::ok("OK"),
+                Err(e) => Response::error(format!("Unable to handle event, message:{:?}", e), 500),
+            }
+        },
+        SlackEventRequest::Unsupported => Response::ok("OK"),
+    }
+}
+
+pub async fn interactions(mut req: Request, ctx: RouteContext<Context>) -> Result<Response> {
+    let body = req.text().await?;
+
+    if !verify_slack_request(&req, &body, &ctx)? {
+        return Response::error("Invalid signature", 401);
+    }
+
+    let params: InteractionRequest = serde_qs::from_str(&body)
+        .map_err(|e| worker::Error::RustError(format!("{}", e)))?;
+
+    let payload: InteractionPayload = serde_json::from_str(&params.payload)?;
+
+    match handle_interaction(payload, &ctx).await {
+        Ok(Some(response)) => Response::from_json(&response),
+        Ok(None) => Response::ok(""),
+        Err(e) => Response::error(format!("Unable to handle interaction, message:{:?}", e), 500),
+    }
+}
+
+const SLACK_SCOPES: &str = "app_mentions:read,channels:history,chat:write,commands,groups:history,im:write,links:read,links:write,reactions:write,users:read,users:read.email";
+
+pub async fn install(req: Request, ctx: RouteContext<Context>) -> Result<Response> {
+    let state = Slack.oauth_state(&ctx.secret("SLACK_CLIENT_SECRET")?.to_string(), Date::now().as_millis() / 1000);
+
+    let authorize_url = Url::parse_with_params(
+        "https://slack.com/oauth/v2/authorize",
+        &[
+            ("client_id", ctx.var("SLACK_CLIENT_ID")?.to_string()),
+            ("scope", SLACK_SCOPES.to_string()),
+            ("redirect_uri", oauth_redirect_uri(&req)?),
+            ("state", state.to_string()),
+        ],
+    )?;
+
+    // The state is also kept in a cookie so the callback only completes in the browser that started the flow.
+    let mut headers = Headers::new();
+    headers.set("Location", authorize_url.as_str())?;
+    headers.set("Set-Cookie", &format!("slack_oauth_state={}; Path=/slack/oauth; Max-Age=600; HttpOnly; Secure; SameSite=Lax", state))?;
+
+    Ok(Response::empty()?.with_status(302).with_headers(headers))
+}
+
+pub async fn oauth_callback(req: Request, ctx: RouteContext<Context>) -> Result<Response> {
+    let url = req.url()?;
+    let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.to_string());
+
+    if let Some(error) = param("error") {
+        return Response::error(format!("Installation was cancelled: {}", error), 400);
+    }
+
+    let (code, state) = match (param("code"), param("state")) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Response::error("Missing code or state", 400),
+    };
+
+    let client_secret = ctx.secret("SLACK_CLIENT_SECRET")?.to_string();
+    let cookie = req.headers().get("Cookie")?.unwrap_or_default();
+
+    let is_same_browser = cookie
+        .split(';')
+        .any(|pair| pair.trim() == format!("slack_oauth_state={}", state));
+
+    if !is_same_browser || !Slack.verify_oauth_state(&client_secret, &state, Date::now().as_millis() / 1000) {
+        return Response::error("Invalid state", 400);
+    }
+
+    let result = Slack
+        .oauth_access(
+            &ctx.var("SLACK_CLIENT_ID")?.to_string(),
+            &client_secret,
+            &code,
+            &oauth_redirect_uri(&req)?,
+        )
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    let installation = match result {
+        Ok(res) => Installation::from_oauth(res, Date::now().as_millis() / 1000),
+        Err(e) => return Response::error(format!("Unable to complete installation, message:{:?}", e), 500),
+    };
+
+    let result = Store
+        .save_installation(&ctx.kv("STORE")?, &installation)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    match result {
+        Ok(_) => Response::from_html("<p>The app has been installed. You can close this window.</p>"),
+        Err(e) => Response::error(format!("Unable to complete installation, message:{:?}", e), 500),
+    }
+}
+
+pub async fn github_oauth_callback(req: Request, ctx: RouteContext<Context>) -> Result<Response> {
+    let url = req.url()?;
+    let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.to_string());
+
+    let (code, state) = match (param("code"), param("state")) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Response::error("Missing code or state", 400),
+    };
+
+    let client_secret = ctx.secret("GITHUB_CLIENT_SECRET")?.to_string();
+
+    let (team_id, user_id, login) = match Slack.verify_link_state(&client_secret, &state, Date::now().as_millis() / 1000) {
+        Some(linking) => linking,
+        None => return Response::error("Invalid state", 400),
+    };
+
+    let result = match Github.oauth_access_token(&ctx.var("GITHUB_CLIENT_ID")?.to_string(), &client_secret, &code).await {
+            Ok(token) => Github.fetch_authenticated_user(&token).await,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    let github_user = match result {
+        Ok(github_user) => github_user,
+        Err(e) => return Response::error(format!("Unable to verify GitHub account, message:{:?}", e), 500),
+    };
+
+    // Whoever holds the link must also hold the GitHub account that was asked for.
+    if !github_user.login.eq_ignore_ascii_case(&login) {
+        return Response::error(format!("Signed in as {}, not {}", github_user.login, login), 403);
+    }
+
+    let result = Store
+        .link_user(&ctx.kv("STORE")?, &team_id, &user_id, &github_user.login)
+        .await
+        .map_err(|e| worker::Error::RustError(format!("{}", e)));
+
+    match result {
+        Ok(_) => Response::from_html(format!("<p>Your Slack account is now linked to {}. You can close this window.</p>", github_user.login)),
+        Err(e) => Response::error(format!("Unable to link account, message:{:?}", e), 500),
+    }
+}
+
+fn github_redirect_uri(req: &Request) -> Result<String> {
+    let mut url = req.url()?;
+
+    url.set_path("/github/oauth/callback");
+    url.set_query(None);
+
+    Ok(url.to_string())
+}
+
+fn oauth_redirect_uri(req: &Request) -> Result<String> {
+    let mut url = req.url()?;
+
+    url.set_path("/slack/oauth/callback");
+    url.set_query(None);
+
+    Ok(url.to_string())
+}
+
+fn verify_slack_request(req: &Request, body: &str, ctx: &RouteContext<Context>) -> Result<bool> {
+    let timestamp = req.headers().get("X-Slack-Request-Timestamp")?.unwrap_or_default();
+    let signature = req.headers().get("X-Slack-Signature")?.unwrap_or_default();
+
+    Ok(Slack.verify_signature(
+        &ctx.secret("SLACK_SIGNING_SECRET")?.to_string(),
+        &timestamp,
+        body,
+        &signature,
+        Date::now().as_millis() / 1000,
+    ))
+}
+
 #[cfg(test)]
 mod routes_test {
-    use crate::adapter::slack::Slack;
+    use serde_json::{json, Value};
+
+    use crate::adapter::{github::Github, slack::Slack};
     use crate::domain::model::{
-        github::{Issue, User, PullRequest},
-        routes::{GithubWebhookRequest, ReqOwner, ReqRepo},
-        slack::{Accessory, Message, Blocks, Text},
+        filter::FilterEvent,
+        github::{CardState, Issue, User, PullRequest, State},
+        github::IssueReference,
+        rollup::ItemKind,
+        routes::{AlertWebhookRequest, DirectNotification, GithubWebhookRequest, InteractionRequest, NotificationReason, ReqOwner, ReqRepo, SlackEventRequest},
+        block_kit::{Block, Element, Text},
+        slack::{
+            Message, SlackEvent, AppMentionEvent, CardAction, InteractionPayload, LinkSharedEvent,
+            MessageEvent, PostedMessage, ReactionChange, SharedLink, ShortcutOrigin,
+        },
     };
 
     #[test]
@@ -87,10 +662,13 @@ mod routes_test {
             html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
             title: "test".to_string(),
             body: Some("body".to_string()),
-            state: "open".to_string(),
+            state: State::Open,
+            state_reason: None,
+            labels: vec![],
             created_at: "2024-07-07T20:09:31Z".to_string(),
             number: 1,
             user: lookup_user,
+            pull_request: None,
         };
 
         let body = "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&enterprise_id=E0001&enterprise_name=Globular%2520Construct%2520Inc&channel_id=C2147483705&channel_name=test&user_id=U2147483697&user_name=Steve&command=%2Fissue&text=cloudflare%2Fwrangler%231&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=13345224609.738474920.8088930838d88f008e0root@d1cdcb320e3f".to_string();
@@ -100,10 +678,12 @@ mod routes_test {
                 &issue,
                 &Slack.text_lines(&issue, &body, "")
             ),
+            attachments: vec![],
             response_type: "in_channel".to_string(),
+            text: String::new(),
         };
 
-        let slack_message: Message = serde_json::from_str(r#"{"blocks":[{"type":"section","text":{"type":"mrkdwn","text":"*test - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\nbody\n*open* - Created by <https://github.com/signalnerve|test> on 2024-07-07 20:09:31"},"accessory":{"type":"image","image_url":"https://github.com/images/error/octocat_happy.gif","alt_text":"test"}}],"response_type":"in_channel"}"#).unwrap();
+        let slack_message: Message = serde_json::from_str(r#"{"blocks":[{"type":"section","text":{"type":"mrkdwn","text":"*test - <https://github.com/cloudflare/wrangler-legacy/issues/1|>*\nbody\n*open* - Created by <https://github.com/signalnerve|test\u2063> on 2024-07-07 20:09:31"},"accessory":{"type":"image","image_url":"https://github.com/images/error/octocat_happy.gif","alt_text":"test"}}],"response_type":"in_channel"}"#).unwrap();
 
         assert_eq!(serde_json::json!(&slack_message), serde_json::json!(&result));
     }
@@ -116,22 +696,32 @@ mod routes_test {
                 html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
                 title: "test".to_string(),
                 body: Some("body".to_string()),
-                state: "open".to_string(),
+                state: State::Open,
+                state_reason: None,
+                labels: vec![],
                 created_at: "2024-07-07T20:09:31Z".to_string(),
                 number: 1,
                 user: User {
                     html_url: "https://github.com/signalnerve".to_string(),
                     login: "test".to_string(),
                     avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-                }
+                },
+                pull_request: None,
             }),
             pull_request: None,
+            review: None,
+            comment: None,
+            requested_reviewer: None,
+            assignee: None,
+            sender: None,
+            release: None,
             repository: ReqRepo {
                 name: "test_repo".to_string(),
                 owner: ReqOwner {
                     login: "test".to_string(),
                 }
             },
+            event_name: None,
         };
 
         let issue = body.issue.clone().unwrap();
@@ -144,18 +734,13 @@ mod routes_test {
 
         let result = Slack.construct_message(&issue, &text_lines);
 
-        assert_eq!(vec![Blocks {
-            r#type: "section".to_string(),
-            text: Text {
-                r#type: "mrkdwn".to_string(),
-                text: text_lines,
-            },
-            accessory: Accessory {
-                r#type: "image".to_string(),
-                image_url: issue.user.avatar_url,
-                alt_text: issue.user.login,
-            },
-        }], result);
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn(&text_lines))
+                    .with_accessory(Element::image(&issue.user.avatar_url, &issue.user.login))
+            ],
+            result
+        );
 
     }
 
@@ -167,22 +752,32 @@ mod routes_test {
                 html_url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
                 title: "test".to_string(),
                 body: None,
-                state: "open".to_string(),
+                state: State::Open,
+                state_reason: None,
+                labels: vec![],
                 created_at: "2024-07-07T20:09:31Z".to_string(),
                 number: 1,
                 user: User {
                     html_url: "https://github.com/signalnerve".to_string(),
                     login: "test".to_string(),
                     avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-                }
+                },
+                pull_request: None,
             }),
             pull_request: None,
+            review: None,
+            comment: None,
+            requested_reviewer: None,
+            assignee: None,
+            sender: None,
+            release: None,
             repository: ReqRepo {
                 name: "test_repo".to_string(),
                 owner: ReqOwner {
                     login: "test".to_string(),
                 }
             },
+            event_name: None,
         };
 
         let issue = body.issue.clone().unwrap();
@@ -195,18 +790,13 @@ mod routes_test {
 
         let result = Slack.construct_message(&issue, &text_lines);
 
-        assert_eq!(vec![Blocks {
-            r#type: "section".to_string(),
-            text: Text {
-                r#type: "mrkdwn".to_string(),
-                text: text_lines,
-            },
-            accessory: Accessory {
-                r#type: "image".to_string(),
-                image_url: issue.user.avatar_url,
-                alt_text: issue.user.login,
-            },
-        }], result);
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn(&text_lines))
+                    .with_accessory(Element::image(&issue.user.avatar_url, &issue.user.login))
+            ],
+            result
+        );
 
     }
 
@@ -218,22 +808,34 @@ mod routes_test {
                 html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
                 title: "test pull_request".to_string(),
                 body: Some("pull_request body".to_string()),
-                state: "open".to_string(),
+                state: State::Open,
+                draft: false,
+                labels: vec![],
                 created_at: "2024-07-07T20:09:31Z".to_string(),
                 number: 2,
                 user: User {
                     html_url: "https://github.com/reo0306/".to_string(),
                     login: "test2".to_string(),
                     avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-                }
+                },
+                merged: false,
+                merged_by: None,
+                base: None,
             }),
             issue: None,
+            review: None,
+            comment: None,
+            requested_reviewer: None,
+            assignee: None,
+            sender: None,
+            release: None,
             repository: ReqRepo {
                 name: "test_repo2".to_string(),
                 owner: ReqOwner {
                     login: "test2".to_string(),
                 }
             },
+            event_name: None,
         };
 
         let pull_request = body.pull_request.clone().unwrap();
@@ -246,19 +848,254 @@ mod routes_test {
 
         let result = Slack.construct_message(&pull_request, &text_lines);
 
-        assert_eq!(vec![Blocks {
-            r#type: "section".to_string(),
-            text: Text {
-                r#type: "mrkdwn".to_string(),
-                text: text_lines,
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn(&text_lines))
+                    .with_accessory(Element::image(&pull_request.user.avatar_url, &pull_request.user.login))
+            ],
+            result
+        );
+
+    }
+
+    #[test]
+    fn test_thread_reply() {
+        let body = GithubWebhookRequest {
+            action: "closed".to_string(),
+            pull_request: Some(PullRequest {
+                html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
+                title: "test pull_request".to_string(),
+                body: Some("pull_request body".to_string()),
+                state: State::Closed,
+                draft: false,
+                labels: vec![],
+                created_at: "2024-07-07T20:09:31Z".to_string(),
+                number: 2,
+                user: User {
+                    html_url: "https://github.com/reo0306/".to_string(),
+                    login: "test2".to_string(),
+                    avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
+                },
+                merged: false,
+                merged_by: None,
+                base: None,
+            }),
+            issue: None,
+            review: None,
+            comment: None,
+            requested_reviewer: None,
+            assignee: None,
+            sender: None,
+            release: None,
+            repository: ReqRepo {
+                name: "test_repo2".to_string(),
+                owner: ReqOwner {
+                    login: "test2".to_string(),
+                }
             },
-            accessory: Accessory {
-                r#type: "image".to_string(),
-                image_url: pull_request.user.avatar_url,
-                alt_text: pull_request.user.login,
+            event_name: None,
+        };
+
+        assert_eq!(
+            Some(IssueReference {
+                owner: "test2".to_string(),
+                repo: "test_repo2".to_string(),
+                number: 2,
+            }),
+            body.reference()
+        );
+        assert!(body.is_important());
+
+        let result = Slack.create_thread_reply(body.pull_request.as_ref().unwrap(), &body);
+
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn("An Pull request was closed: <https://github.com/reo0306/rust-todo-di-app/pull/1|test pull_request>"))
+            ],
+            result.blocks
+        );
+    }
+
+    /// `fields` over `base`, both JSON objects.
+    fn with(mut base: Value, fields: Value) -> Value {
+        if let (Some(base), Value::Object(fields)) = (base.as_object_mut(), fields) {
+            base.extend(fields);
+        }
+
+        base
+    }
+
+    fn user(login: &str) -> Value {
+        json!({"html_url": format!("https://github.com/{}", login), "login": login, "avatar_url": format!("https://github.com/{}.png", login)})
+    }
+
+    fn issue(fields: Value) -> Value {
+        with(json!({"html_url": "https://github.com/o/r/issues/1", "title": "t", "body": null, "state": "open", "created_at": "2024-07-07T20:09:31Z", "number": 1, "user": user("u")}), fields)
+    }
+
+    fn pull_request(fields: Value) -> Value {
+        with(json!({"html_url": "https://github.com/o/r/pull/1", "title": "t", "body": null, "state": "open", "created_at": "2024-07-07T20:09:31Z", "number": 1, "user": user("u"), "merged": false, "merged_by": null}), fields)
+    }
+
+    /// A webhook for `o/r`; `fields` carry the item and whatever else the event sends.
+    fn webhook(action: &str, fields: Value) -> GithubWebhookRequest {
+        serde_json::from_value(with(json!({"action": action, "repository": {"name": "r", "owner": {"login": "o"}}}), fields)).unwrap()
+    }
+
+    #[test]
+    fn test_review_reactions() {
+        let reactions = |action: &str, review: Value, merged: bool| webhook(action, json!({"review": review, "pull_request": pull_request(json!({"merged": merged}))})).reactions();
+
+        assert_eq!(Some(ReactionChange { add: vec!["eyes"], remove: vec![] }), reactions("review_requested", Value::Null, false));
+        assert_eq!(Some(ReactionChange { add: vec!["white_check_mark"], remove: vec!["eyes", "x"] }), reactions("submitted", json!({"state": "approved"}), false));
+        assert_eq!(Some(ReactionChange { add: vec!["x"], remove: vec!["eyes", "white_check_mark"] }), reactions("submitted", json!({"state": "changes_requested"}), false));
+        assert_eq!(None, reactions("submitted", json!({"state": "commented"}), false));
+        assert_eq!(Some(ReactionChange { add: vec!["tada"], remove: vec!["eyes"] }), reactions("closed", Value::Null, true));
+        assert_eq!(None, reactions("closed", Value::Null, false));
+    }
+
+    #[test]
+    fn test_webhook_card_state() {
+        let card_state = |action: &str| {
+            let body = webhook(action, json!({"pull_request": pull_request(json!({}))}));
+
+            body.card_state(body.pull_request.as_ref().unwrap())
+        };
+
+        assert_eq!(CardState::Opened, card_state("opened"));
+        assert_eq!(CardState::Reopened, card_state("reopened"));
+
+        let body = webhook("opened", json!({"pull_request": pull_request(json!({}))}));
+
+        assert_eq!("pull_request", body.event());
+        assert_eq!("o/r", body.full_name());
+
+        let mut body = webhook("created", json!({"comment": {"html_url": "https://github.com/o/r/pull/1#discussion_r1", "body": "nit"}, "pull_request": pull_request(json!({}))}));
+
+        assert_eq!("pull_request_review_comment", body.event());
+
+        body.event_name = Some("issue_comment".to_string());
+
+        assert_eq!("issue_comment", body.event());
+    }
+
+    #[test]
+    fn test_filter_event() {
+        let body = serde_json::from_str::<GithubWebhookRequest>(r#"{"action":"labeled","pull_request":{"html_url":"https://github.com/o/r/pull/1","title":"t","body":null,"state":"open","draft":true,"labels":[{"name":"infra","color":"ededed"}],"base":{"ref":"main","sha":"abc"},"created_at":"2024-07-07T20:09:31Z","number":1,"user":{"html_url":"https://github.com/u","login":"u","avatar_url":"https://github.com/u.png"},"merged":false,"merged_by":null},"repository":{"name":"r","owner":{"login":"o"}}}"#).unwrap();
+
+        assert_eq!(
+            FilterEvent {
+                pull_request: true,
+                action: "labeled".to_string(),
+                author: Some("u".to_string()),
+                labels: vec!["infra".to_string()],
+                base: Some("main".to_string()),
+                draft: Some(true),
+                paths: vec![],
             },
-        }], result);
+            body.filter_event()
+        );
+    }
+
+    #[test]
+    fn test_bot_activity() {
+        let body = |action: &str, review: Value, sender: &str| webhook(action, json!({
+            "review": review,
+            "pull_request": pull_request(json!({"html_url": "https://github.com/o/r/pull/3", "title": "Bump serde", "state": "closed", "number": 3, "user": user("dependabot[bot]"), "merged": true})),
+            "sender": user(sender),
+        }));
+
+        assert!(body("closed", Value::Null, "octocat").is_bot_activity(&[]));
+        assert!(!body("submitted", json!({"state": "approved"}), "octocat").is_bot_activity(&[]));
+        assert!(body("submitted", json!({"state": "approved"}), "ci-runner").is_bot_activity(&["ci-runner".to_string()]));
+
+        let entry = body("closed", Value::Null, "octocat").rollup_entry().unwrap();
+
+        assert_eq!("merged", entry.action);
+        assert_eq!("o/r", entry.repository);
+        assert_eq!(ItemKind::PullRequest, entry.kind);
+        assert_eq!(Some("octocat".to_string()), entry.actor);
+    }
+
+    #[test]
+    fn test_release_event() {
+        let body = serde_json::from_str::<GithubWebhookRequest>(r#"{"action":"published","release":{"tag_name":"v1.2.0","name":"","html_url":"https://github.com/o/r/releases/tag/v1.2.0","author":{"html_url":"https://github.com/u","login":"u","avatar_url":"https://github.com/u.png"}},"sender":{"html_url":"https://github.com/u","login":"u","avatar_url":"https://github.com/u.png"},"repository":{"name":"r","owner":{"login":"o"}}}"#).unwrap();
+        let release = body.release.as_ref().unwrap();
+
+        assert_eq!("release", body.event());
+        assert!(body.reference().is_none());
+
+        let entry = body.rollup_entry().unwrap();
+
+        assert_eq!(ItemKind::Release, entry.kind);
+        assert_eq!("v1.2.0", entry.title);
+        assert_eq!(
+            ":package: o/r released <https://github.com/o/r/releases/tag/v1.2.0|v1.2.0>",
+            Slack.create_release_message(&body, release).text
+        );
+    }
+
+    #[test]
+    fn test_alert() {
+        let deployment = |state: &str| serde_json::from_value::<AlertWebhookRequest>(json!({
+            "action": "created",
+            "deployment_status": {"state": state, "environment": "production", "target_url": null, "log_url": "https://github.com/o/r/actions/runs/1"},
+            "deployment": {"ref": "main"},
+            "repository": {"name": "r", "owner": {"login": "o"}},
+        })).unwrap();
+
+        assert!(deployment("failure").is_urgent());
+        assert!(deployment("error").is_urgent());
+        assert!(!deployment("success").is_urgent());
+        assert_eq!(
+            ":rotating_light: Deployment of `main` to <https://github.com/o/r/actions/runs/1|production> failed in o/r",
+            Slack.create_alert_message(&deployment("failure")).unwrap().text
+        );
+
+        let advisory = serde_json::from_value::<AlertWebhookRequest>(json!({
+            "action": "published",
+            "security_advisory": {"ghsa_id": "GHSA-abcd-1234-efgh", "summary": "RCE in <parser>", "severity": "critical"},
+        })).unwrap();
+
+        assert!(advisory.is_urgent());
+        assert_eq!(None, advisory.full_name());
+        assert_eq!(
+            ":rotating_light: critical security advisory <https://github.com/advisories/GHSA-abcd-1234-efgh|RCE in &lt;parser&gt;>",
+            Slack.create_alert_message(&advisory).unwrap().text
+        );
+    }
+
+    #[test]
+    fn test_direct_notifications() {
+        let notifications = |action: &str, extra: Value| webhook(action, with(json!({
+            "issue": issue(json!({"body": "cc @hubot, @org/team and me@example.com"})),
+            "sender": user("octocat"),
+        }), extra)).direct_notifications();
+        let notification = |login: &str, reason| DirectNotification { login: login.to_string(), reason };
+
+        assert_eq!(vec![notification("hubot", NotificationReason::Mentioned)], notifications("opened", json!({})));
+        assert_eq!(vec![notification("monalisa", NotificationReason::Assigned)], notifications("assigned", json!({"assignee": user("monalisa")})));
+        assert_eq!(
+            vec![notification("monalisa", NotificationReason::Mentioned)],
+            notifications("created", json!({"comment": {"html_url": "https://github.com/o/r/issues/1#issuecomment-1", "body": "@monalisa @octocat what do you think?"}}))
+        );
+        assert_eq!(Vec::<DirectNotification>::new(), notifications("assigned", json!({"assignee": user("octocat")})));
+        assert_eq!(Vec::<DirectNotification>::new(), notifications("labeled", json!({})));
+    }
+
+    #[test]
+    fn test_item_deleted() {
+        assert!(webhook("deleted", json!({"issue": issue(json!({}))})).is_item_deleted());
+        assert!(!webhook("deleted", json!({"issue": issue(json!({})), "comment": {"html_url": "https://github.com/o/r/issues/1#issuecomment-1", "body": "typo"}})).is_item_deleted());
+    }
+
+    #[test]
+    fn test_github_signature() {
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
 
+        assert!(Github.verify_signature("It's a Secret to Everybody", "Hello, World!", signature));
+        assert!(!Github.verify_signature("It's a Secret to Everybody", "Hello, World?", signature));
+        assert!(!Github.verify_signature("It's a Secret to Everybody", "Hello, World!", "757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"));
     }
 
     #[test]
@@ -269,22 +1106,34 @@ mod routes_test {
                 html_url: "https://github.com/reo0306/rust-todo-di-app/pull/1".to_string(),
                 title: "test pull_request".to_string(),
                 body: None,
-                state: "open".to_string(),
+                state: State::Open,
+                draft: false,
+                labels: vec![],
                 created_at: "2024-07-07T20:09:31Z".to_string(),
                 number: 2,
                 user: User {
                     html_url: "https://github.com/reo0306/".to_string(),
                     login: "test2".to_string(),
                     avatar_url: "https://github.com/images/error/octocat_happy.gif".to_string(),
-                }
+                },
+                merged: false,
+                merged_by: None,
+                base: None,
             }),
             issue: None,
+            review: None,
+            comment: None,
+            requested_reviewer: None,
+            assignee: None,
+            sender: None,
+            release: None,
             repository: ReqRepo {
                 name: "test_repo2".to_string(),
                 owner: ReqOwner {
                     login: "test2".to_string(),
                 }
             },
+            event_name: None,
         };
 
         let pull_request = body.pull_request.clone().unwrap();
@@ -297,18 +1146,195 @@ mod routes_test {
 
         let result = Slack.construct_message(&pull_request, &text_lines);
 
-        assert_eq!(vec![Blocks {
-            r#type: "section".to_string(),
-            text: Text {
-                r#type: "mrkdwn".to_string(),
-                text: text_lines,
+        assert_eq!(
+            vec![
+                Block::section(Text::mrkdwn(&text_lines))
+                    .with_accessory(Element::image(&pull_request.user.avatar_url, &pull_request.user.login))
+            ],
+            result
+        );
+
+    }
+
+    #[test]
+    fn test_url_verification() {
+        let body = r#"{"token":"Jhj5dZrVaK7ZwHHjRyZWjbDl","challenge":"3eZbrw1aBm2rZgRNFdxV2595E9CY3gmdALWMmHkvFXO7tYXAYM8P","type":"url_verification"}"#;
+
+        match serde_json::from_str::<SlackEventRequest>(body).unwrap() {
+            SlackEventRequest::UrlVerification { challenge } => assert_eq!("3eZbrw1aBm2rZgRNFdxV2595E9CY3gmdALWMmHkvFXO7tYXAYM8P", challenge),
+            _ => panic!("expected url_verification"),
+        }
+    }
+
+    #[test]
+    fn test_event_callback() {
+        let body = r#"{"token":"XXYYZZ","team_id":"T123ABC456","api_app_id":"A123ABC456","event":{"type":"app_mention","user":"U123ABC456","text":"<@U0LAN0Z89> is it everything a river should be?","ts":"1515449522.000016","channel":"C123ABC456","event_ts":"1515449522000016"},"type":"event_callback","event_id":"Ev123ABC456","event_time":1515449522000016}"#;
+
+        match serde_json::from_str::<SlackEventRequest>(body).unwrap() {
+            SlackEventRequest::EventCallback { team_id, event, .. } => {
+                assert_eq!("T123ABC456", team_id);
+                assert_eq!(SlackEvent::AppMention(AppMentionEvent {
+                    channel: "C123ABC456".to_string(),
+                    user: "U123ABC456".to_string(),
+                    text: "<@U0LAN0Z89> is it everything a river should be?".to_string(),
+                    ts: "1515449522.000016".to_string(),
+                    thread_ts: None,
+                }), event);
             },
-            accessory: Accessory {
-                r#type: "image".to_string(),
-                image_url: pull_request.user.avatar_url,
-                alt_text: pull_request.user.login,
+            _ => panic!("expected event_callback"),
+        }
+    }
+
+    #[test]
+    fn test_app_mention_text_without_mentions() {
+        let event = AppMentionEvent {
+            channel: "C123ABC456".to_string(),
+            user: "U123ABC456".to_string(),
+            text: "<@U0LAN0Z89> status org/repo#12".to_string(),
+            ts: "1515449522.000016".to_string(),
+            thread_ts: None,
+        };
+
+        assert_eq!("status org/repo#12", event.text_without_mentions());
+    }
+
+    #[test]
+    fn test_message_event_is_user_message() {
+        let mut event = MessageEvent {
+            channel: "C123ABC456".to_string(),
+            user: Some("U123ABC456".to_string()),
+            text: Some("see org/repo#12".to_string()),
+            ts: "1515449522.000016".to_string(),
+            thread_ts: None,
+            subtype: None,
+            bot_id: None,
+        };
+
+        assert!(event.is_user_message());
+
+        event.subtype = Some("message_changed".to_string());
+        assert!(!event.is_user_message());
+
+        event.subtype = None;
+        event.bot_id = Some("B123ABC456".to_string());
+        assert!(!event.is_user_message());
+    }
+
+    #[test]
+    fn test_block_actions() {
+        let body = "payload=%7B%22type%22%3A%22block_actions%22%2C%22user%22%3A%7B%22id%22%3A%22U123ABC456%22%2C%22username%22%3A%22steve%22%7D%2C%22team%22%3A%7B%22id%22%3A%22T123ABC456%22%2C%22domain%22%3A%22example%22%7D%2C%22response_url%22%3A%22https%3A%2F%2Fhooks.slack.com%2Factions%2FT123ABC456%2F1%2Fabc%22%2C%22actions%22%3A%5B%7B%22type%22%3A%22static_select%22%2C%22action_id%22%3A%22add_label%22%2C%22block_id%22%3A%22x%22%2C%22selected_option%22%3A%7B%22text%22%3A%7B%22type%22%3A%22plain_text%22%2C%22text%22%3A%22bug%22%7D%2C%22value%22%3A%22org%2Frepo%2312%3Abug%22%7D%7D%2C%7B%22type%22%3A%22button%22%2C%22action_id%22%3A%22close%22%2C%22block_id%22%3A%22x%22%2C%22value%22%3A%22org%2Frepo%2312%22%7D%5D%7D";
+
+        let params: InteractionRequest = serde_qs::from_str(body).unwrap();
+
+        let reference = IssueReference {
+            owner: "org".to_string(),
+            repo: "repo".to_string(),
+            number: 12,
+        };
+
+        match serde_json::from_str::<InteractionPayload>(&params.payload).unwrap() {
+            InteractionPayload::BlockActions(payload) => {
+                assert_eq!("U123ABC456", payload.user.id);
+                assert_eq!(
+                    vec![
+                        CardAction::AddLabel(reference.clone(), "bug".to_string()),
+                        CardAction::Close(reference),
+                    ],
+                    payload.actions.iter().filter_map(|action| action.card_action()).collect::<Vec<_>>()
+                );
             },
-        }], result);
+            _ => panic!("expected block_actions"),
+        }
+    }
+
+    #[test]
+    fn test_block_actions_container() {
+        let payload = r#"{"type":"block_actions","user":{"id":"U123ABC456"},"container":{"type":"message","message_ts":"1515449522.000016","channel_id":"C123ABC456","is_ephemeral":false},"actions":[]}"#;
+
+        match serde_json::from_str::<InteractionPayload>(payload).unwrap() {
+            InteractionPayload::BlockActions(payload) => {
+                assert_eq!(
+                    Some(PostedMessage {
+                        channel: "C123ABC456".to_string(),
+                        ts: "1515449522.000016".to_string(),
+                    }),
+                    payload.container.and_then(|container| container.message())
+                );
+            },
+            _ => panic!("expected block_actions"),
+        }
+    }
+
+    #[test]
+    fn test_view_submission() {
+        let payload = r#"{"type":"view_submission","team":{"id":"T123ABC456"},"user":{"id":"U123ABC456"},"view":{"id":"V123ABC456","hash":"156663117.cd33ad1f","callback_id":"create_issue","private_metadata":"{\"channel\":\"C123ABC456\",\"thread_ts\":\"1515449522.000016\"}","state":{"values":{"repository":{"select_repository":{"type":"static_select","selected_option":{"text":{"type":"plain_text","text":"org/repo"},"value":"org/repo"}}},"labels":{"labels":{"type":"multi_static_select","selected_options":[{"text":{"type":"plain_text","text":"bug"},"value":"bug"},{"text":{"type":"plain_text","text":"p1"},"value":"p1"}]}},"title":{"title":{"type":"plain_text_input","value":"Login fails"}},"body":{"body":{"type":"plain_text_input","value":null}}}}}}"#;
+
+        match serde_json::from_str::<InteractionPayload>(payload).unwrap() {
+            InteractionPayload::ViewSubmission(payload) => {
+                let state = &payload.view.state;
+
+                assert_eq!(Some("org/repo"), state.selected("repository"));
+                assert_eq!(vec!["bug", "p1"], state.selected_all("labels"));
+                assert_eq!(Some("Login fails"), state.text("title"));
+                assert_eq!(None, state.text("body"));
+                assert_eq!(
+                    ShortcutOrigin { channel: "C123ABC456".to_string(), thread_ts: "1515449522.000016".to_string() },
+                    serde_json::from_str(&payload.view.private_metadata).unwrap()
+                );
+            },
+            _ => panic!("expected view_submission"),
+        }
+    }
+
+    #[test]
+    fn test_event_callback_link_shared() {
+        let body = r#"{"team_id":"T123ABC456","event":{"type":"link_shared","channel":"C123ABC456","is_bot_user_member":true,"user":"U123ABC456","message_ts":"123456789.9875","unfurl_id":"C123456.123456789.987501","source":"conversations_history","links":[{"domain":"github.com","url":"https://github.com/cloudflare/wrangler-legacy/issues/1"}]},"type":"event_callback"}"#;
+
+        match serde_json::from_str::<SlackEventRequest>(body).unwrap() {
+            SlackEventRequest::EventCallback { event, .. } => {
+                assert_eq!(SlackEvent::LinkShared(LinkSharedEvent {
+                    channel: "C123ABC456".to_string(),
+                    user: "U123ABC456".to_string(),
+                    message_ts: "123456789.9875".to_string(),
+                    links: vec![SharedLink {
+                        domain: "github.com".to_string(),
+                        url: "https://github.com/cloudflare/wrangler-legacy/issues/1".to_string(),
+                    }],
+                    unfurl_id: Some("C123456.123456789.987501".to_string()),
+                    source: Some("conversations_history".to_string()),
+                }), event);
+            },
+            _ => panic!("expected event_callback"),
+        }
+    }
+
+    #[test]
+    fn test_event_callback_uninstall() {
+        let body = r#"{"team_id":"T123ABC456","event":{"type":"app_uninstalled"},"type":"event_callback"}"#;
+
+        match serde_json::from_str::<SlackEventRequest>(body).unwrap() {
+            SlackEventRequest::EventCallback { event, .. } => assert_eq!(SlackEvent::AppUninstalled, event),
+            _ => panic!("expected event_callback"),
+        }
+
+        let body = r#"{"team_id":"T123ABC456","event":{"type":"tokens_revoked","tokens":{"bot":["U0LAN0Z89"]}},"type":"event_callback"}"#;
+
+        match serde_json::from_str::<SlackEventRequest>(body).unwrap() {
+            SlackEventRequest::EventCallback { event: SlackEvent::TokensRevoked(event), .. } => {
+                assert_eq!(vec!["U0LAN0Z89".to_string()], event.tokens.bot);
+                assert!(event.tokens.oauth.is_empty());
+            },
+            _ => panic!("expected tokens_revoked"),
+        }
+    }
+
+    #[test]
+    fn test_event_callback_is_unsupported() {
+        let body = r#"{"team_id":"T123ABC456","event":{"type":"reaction_added","user":"U123ABC456"},"type":"event_callback"}"#;
 
+        match serde_json::from_str::<SlackEventRequest>(body).unwrap() {
+            SlackEventRequest::EventCallback { event, .. } => assert_eq!(SlackEvent::Unsupported, event),
+            _ => panic!("expected event_callback"),
+        }
     }
 }
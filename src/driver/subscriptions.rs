@@ -0,0 +1,121 @@
+use std::error::Error;
+use worker::*;
+
+use crate::adapter::{
+    github::Github,
+    slack::Slack,
+    store::Store,
+};
+use crate::domain::model::{
+    command::Command,
+    digest::Frequency,
+    routes::GithubWebhookRequest,
+    subscription::{Category, Subscriber},
+};
+
+/// Applies a subscription command to `channel` and returns the reply for the user.
+pub async fn manage_subscriptions(team_id: &str, channel: &str, command: Command, ctx: &RouteContext<Context>) -> std::result::Result<String, Box<dyn Error>> {
+    let kv = ctx.kv("STORE")?;
+    let mut subscriptions = Store.channel_subscriptions(&kv, team_id, channel).await?;
+
+    let repository = match command {
+        Command::Subscribe { repository, categories } => {
+            let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+            let (owner, repo) = repository.split_once('/').unwrap_or_default();
+
+            let found = match Github.fetch_repository(&github_token, owner, repo).await {
+                Ok(found) => found,
+                Err(_) => return Ok(format!("{} does not exist or is not visible to the bot's GitHub account", repository)),
+            };
+
+            // Same rule as for link previews: private repositories only reach channels they were shared with.
+            if found.private && !Store.repository_channels(&kv, &found.full_name).await?.iter().any(|c| c == channel) {
+                return Ok(format!("{} is private and not shared with this channel", found.full_name));
+            }
+
+            let categories = if categories.is_empty() { Category::ALL.to_vec() } else { categories };
+            subscriptions.subscribe(&found.full_name, &categories);
+
+            found.full_name
+        },
+        Command::Unsubscribe { repository, categories } => {
+            if subscriptions.subscription(&repository).is_none() {
+                return Ok(format!("This channel is not subscribed to {}", repository));
+            }
+
+            subscriptions.unsubscribe(&repository, &categories);
+
+            repository
+        },
+        Command::Filter { repository, rules } => {
+            if !subscriptions.set_filter(&repository, &rules) {
+                return Ok(format!("This channel is not subscribed to {}", repository));
+            }
+
+            repository
+        },
+        Command::Digest { repository, frequency } => {
+            if !subscriptions.set_digest(&repository, frequency) {
+                return Ok(format!("This channel is not subscribed to {}", repository));
+            }
+
+            repository
+        },
+        _ => return Ok(Slack.subscriptions_text(&subscriptions)),
+    };
+
+    let subscription = subscriptions.subscription(&repository).cloned();
+    let subscriber = Subscriber {
+        team_id: team_id.to_string(),
+        channel: channel.to_string(),
+    };
+
+    Store.save_channel_subscriptions(&kv, team_id, channel, &subscriptions).await?;
+    Store.set_subscriber(&kv, &repository, &subscriber, subscription.as_ref()).await?;
+
+    Ok(Slack.subscription_text(&repository, subscription.as_ref()))
+}
+
+/// Channels subscribed to the category of the event whose filters let it through, with the digest
+/// they collect it for, or `None` when no channel subscribed to the category at all. Changed paths,
+/// and for comments on pull requests the pull request itself, are fetched once and only if a filter needs them.
+pub async fn subscribed_channels(body: &GithubWebhookRequest, ctx: &RouteContext<Context>) -> std::result::Result<Option<Vec<(Subscriber, Option<Frequency>)>>, Box<dyn Error>> {
+    let category = match Category::from_event(body.event()) {
+        Some(category) => category,
+        None => return Ok(None),
+    };
+
+    let kv = ctx.kv("STORE")?;
+    let repository = body.full_name();
+    let mut wanted = vec![];
+
+    for (subscriber, subscription) in Store.subscribers(&kv, &repository).await? {
+        if subscription.wants(category) {
+            wanted.push((subscriber, subscription.filter, subscription.digest));
+        }
+    }
+
+    if wanted.is_empty() {
+        return Ok(None);
+    }
+
+    let mut event = body.filter_event();
+    let reference = body.reference().filter(|_| event.pull_request);
+
+    if let (Some(reference), true) = (&reference, body.pull_request.is_none() && wanted.iter().any(|(_, filter, _)| filter.needs_pull_request())) {
+        let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+        let pr = Github.fetch_pull_request(&github_token, reference).await?;
+
+        event.base = pr.base.map(|base| base.name);
+        event.draft = Some(pr.draft);
+    }
+
+    if let (Some(reference), true) = (&reference, wanted.iter().any(|(_, filter, _)| filter.needs_paths())) {
+        let github_token = ctx.secret("GITHUB_TOKEN")?.to_string();
+        let files = Github.fetch_pull_request_files(&github_token, reference).await?;
+
+        event.paths = files.into_iter().map(|file| file.filename).collect();
+    }
+
+    Ok(Some(wanted.into_iter().filter(|(_, filter, _)| filter.allows(&event)).map(|(subscriber, _, digest)| (subscriber, digest)).collect()))
+}
@@ -3,14 +3,26 @@ mod adapter;
 mod driver;
 
 use worker::*;
-use crate::driver::routes::{lookup, webhook};
+use crate::driver::routes::{events, github_oauth_callback, install, interactions, lookup, oauth_callback, scheduled, webhook};
 
 #[event(fetch, respond_with_errors)]
-async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
-    Router::new()
+async fn fetch(req: Request, env: Env, ctx: Context) -> Result<Response> {
+    // Handlers get the fetch context to defer work past Slack's response deadline.
+    Router::with_data(ctx)
         .post_async("/lookup", lookup)
         .post_async("/webhook", webhook)
+        .post_async("/events", events)
+        .post_async("/interactions", interactions)
+        .get_async("/slack/install", install)
+        .get_async("/slack/oauth/callback", oauth_callback)
+        .get_async("/github/oauth/callback", github_oauth_callback)
         .run(req, env)
         .await
 }
 
+#[event(scheduled)]
+async fn cron(_event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
+    if let Err(e) = scheduled(&env).await {
+        console_error!("Scheduled tasks failed: {}", e);
+    }
+}